@@ -0,0 +1,95 @@
+#![feature(test)]
+
+extern crate spectra;
+extern crate test;
+
+use spectra::anim::edit::*;
+use std::rc::Rc;
+use test::{Bencher, black_box};
+
+const NB_TRACKS: usize = 8;
+const NB_CUTS: usize = 400;
+const DEMO_DUR: Time = 8. * 60.;
+
+// Build cuts spread over an 8-minute demo; consecutive cuts of a track slightly overlap.
+fn cuts(clip: &Rc<Clip<Time>>) -> Vec<Vec<Cut<Time>>> {
+  let cut_dur = DEMO_DUR / NB_CUTS as Time;
+
+  (0..NB_TRACKS).map(|track| {
+    (0..NB_CUTS).map(|i| {
+      let inst_time = i as Time * cut_dur + track as Time * 0.1;
+      Cut::new(0., cut_dur * 1.1, inst_time, clip.clone()).unwrap()
+    }).collect()
+  }).collect()
+}
+
+// Times at which the timeline is played, one per frame at 60 FPS.
+fn frames() -> Vec<Time> {
+  (0..(DEMO_DUR * 60.) as usize).map(|i| i as Time / 60.).collect()
+}
+
+#[bench]
+fn play_indexed(b: &mut Bencher) {
+  let clip = Rc::new(Clip::new("clip", |t| t));
+  let mut timeline = Timeline::new();
+
+  for cuts in cuts(&clip) {
+    let mut track = Track::new();
+
+    for cut in cuts {
+      track.add_cut(cut);
+    }
+
+    timeline.add_track(track);
+  }
+
+  let frames = frames();
+
+  b.iter(|| {
+    for &t in &frames {
+      black_box(timeline.play(t));
+    }
+  });
+}
+
+// Baseline: linear scan of every cut of every track, as Timeline::play used to do.
+//
+// The active cuts are played and resolved as Timeline::play does with tracks having neither blend
+// modes nor overlaps, so that only the lookup differs between both benchmarks.
+#[bench]
+fn play_linear_scan(b: &mut Bencher) {
+  let clip = Rc::new(Clip::new("clip", |t| t));
+  let tracks = cuts(&clip);
+  let frames = frames();
+
+  b.iter(|| {
+    for &t in &frames {
+      let mut active = Vec::new();
+
+      for (track_id, track) in tracks.iter().enumerate() {
+        for cut in track {
+          if cut.inst_time <= t && t <= cut.inst_time + cut.dur() {
+            active.push((track_id, cut));
+          }
+        }
+      }
+
+      let going_on = active.iter().any(|&(_, cut)| cut.inst_time + cut.dur() > t);
+      let mut nodes: Vec<_> = active.into_iter().filter(|&(_, cut)| !going_on || cut.inst_time + cut.dur() > t).filter_map(|(track, cut)| {
+        cut.play(t).map(|node| (track, node))
+      }).collect();
+
+      let played = match nodes.len() {
+        0 => Played::Inactive,
+        1 => nodes.pop().map(|(_, node)| Played::Resolved(node)).unwrap_or(Played::Inactive),
+        _ => {
+          // without blend modes, tracks cannot be blended
+          nodes.sort_by_key(|&(track, _)| track);
+          Played::NoOverlap
+        }
+      };
+
+      black_box(played);
+    }
+  });
+}
@@ -0,0 +1,150 @@
+//! Custom derives for spectra.
+//!
+//! This crate provides the following derives:
+//!
+//! - `Interpolate`: implement `spectra::anim::spline::Interpolate` for a struct by interpolating
+//!   each field with its own `Interpolate` implementation
+//! - `SplineDeserializerAdapter`: implement `spectra::anim::spline::SplineDeserializerAdapter` for
+//!   a struct by deserializing each field with its own adapter
+//!
+//! Both derives only work on structs with named fields. Deriving them lets you key and sample a
+//! whole struct with a single `Spline`:
+//!
+//! ```ignore
+//! #[macro_use] extern crate serde_derive;
+//! #[macro_use] extern crate spectra_derive;
+//!
+//! #[derive(Clone, Copy, Interpolate, SplineDeserializerAdapter)]
+//! struct FogSettings {
+//!   density: f32,
+//!   color: V3<f32>,
+//!   height: f32
+//! }
+//!
+//! let fog: Spline<FogSettings> = …;
+//! ```
+//!
+//! > Note: `SplineDeserializerAdapter` generates a struct deriving `Deserialize`, so you need
+//! > `serde_derive` in scope where you use it.
+
+extern crate proc_macro;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use syn::{Body, DeriveInput, Field, Ident, VariantData};
+
+#[proc_macro_derive(Interpolate)]
+pub fn derive_interpolate(input: TokenStream) -> TokenStream {
+  let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+  let name = &ast.ident;
+  let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+  let fields = named_fields(&ast, "Interpolate");
+  let idents: Vec<_> = fields.iter().map(|field| field.ident.clone().unwrap()).collect();
+
+  let lerp = idents.iter().map(|f| {
+    quote! { #f: ::spectra::anim::spline::Interpolate::lerp(a.#f, b.#f, t) }
+  });
+
+  let cubic_hermite = idents.iter().map(|f| {
+    quote! {
+      #f: ::spectra::anim::spline::Interpolate::cubic_hermite((x.0.#f, x.1), (a.0.#f, a.1), (b.0.#f, b.1), (y.0.#f, y.1), t)
+    }
+  });
+
+  let kochanek_bartels = idents.iter().map(|f| {
+    quote! {
+      #f: ::spectra::anim::spline::Interpolate::kochanek_bartels((x.0.#f, x.1), (a.0.#f, a.1, a.2), (b.0.#f, b.1, b.2), (y.0.#f, y.1), t)
+    }
+  });
+
+  let gen = quote! {
+    impl #impl_generics ::spectra::anim::spline::Interpolate for #name #ty_generics #where_clause {
+      fn lerp(a: Self, b: Self, t: ::spectra::anim::spline::Time) -> Self {
+        #name {
+          #(#lerp),*
+        }
+      }
+
+      fn cubic_hermite(x: (Self, ::spectra::anim::spline::Time),
+                       a: (Self, ::spectra::anim::spline::Time),
+                       b: (Self, ::spectra::anim::spline::Time),
+                       y: (Self, ::spectra::anim::spline::Time),
+                       t: ::spectra::anim::spline::Time)
+                       -> Self {
+        #name {
+          #(#cubic_hermite),*
+        }
+      }
+
+      fn kochanek_bartels(x: (Self, ::spectra::anim::spline::Time),
+                          a: (Self, ::spectra::anim::spline::Time, ::spectra::anim::spline::Tcb),
+                          b: (Self, ::spectra::anim::spline::Time, ::spectra::anim::spline::Tcb),
+                          y: (Self, ::spectra::anim::spline::Time),
+                          t: ::spectra::anim::spline::Time)
+                          -> Self {
+        #name {
+          #(#kochanek_bartels),*
+        }
+      }
+    }
+  };
+
+  gen.parse().unwrap()
+}
+
+#[proc_macro_derive(SplineDeserializerAdapter)]
+pub fn derive_spline_deserializer_adapter(input: TokenStream) -> TokenStream {
+  let ast = syn::parse_derive_input(&input.to_string()).unwrap();
+  let name = &ast.ident;
+  let de_name = Ident::new(format!("__SpectraSplineDeserialized{}", name));
+  let fields = named_fields(&ast, "SplineDeserializerAdapter");
+
+  if !ast.generics.ty_params.is_empty() || !ast.generics.lifetimes.is_empty() {
+    panic!("SplineDeserializerAdapter cannot be derived for generic types");
+  }
+
+  let de_fields = fields.iter().map(|field| {
+    let f = field.ident.clone().unwrap();
+    let ty = &field.ty;
+
+    quote! { #f: <#ty as ::spectra::anim::spline::SplineDeserializerAdapter>::Deserialized }
+  });
+
+  let from_de = fields.iter().map(|field| {
+    let f = field.ident.clone().unwrap();
+    let ty = &field.ty;
+
+    quote! { #f: <#ty as ::spectra::anim::spline::SplineDeserializerAdapter>::from_deserialized(de.#f) }
+  });
+
+  let gen = quote! {
+    #[doc(hidden)]
+    #[allow(non_camel_case_types)]
+    #[derive(Deserialize)]
+    pub struct #de_name {
+      #(#de_fields),*
+    }
+
+    impl ::spectra::anim::spline::SplineDeserializerAdapter for #name {
+      type Deserialized = #de_name;
+
+      fn from_deserialized(de: Self::Deserialized) -> Self {
+        #name {
+          #(#from_de),*
+        }
+      }
+    }
+  };
+
+  gen.parse().unwrap()
+}
+
+// Extract the named fields of a struct, panicking with a meaningful message otherwise.
+fn named_fields<'a>(ast: &'a DeriveInput, derive: &str) -> &'a [Field] {
+  match ast.body {
+    Body::Struct(VariantData::Struct(ref fields)) => fields,
+    _ => panic!("{} can only be derived for structs with named fields", derive)
+  }
+}
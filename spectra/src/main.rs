@@ -1,10 +1,14 @@
 #[macro_use]
 extern crate clap;
+extern crate serde_json;
+extern crate spectra;
 
-use clap::{App, AppSettings, SubCommand};
+use clap::{App, AppSettings, Arg, SubCommand};
+use spectra::anim::edit::TimelineManifest;
 use std::fs::{File, create_dir_all};
 use std::io::Write;
 use std::path::Path;
+use std::process::exit;
 
 include!(concat!(env!("OUT_DIR"), "/resources.rs"));
 
@@ -16,6 +20,11 @@ fn main() {
     .setting(AppSettings::SubcommandRequired)
     .subcommand(SubCommand::with_name("bootstrap")
          .about("Create default resources in your end-user project"))
+    .subcommand(SubCommand::with_name("validate")
+         .about("Check a timeline manifest for mistakes")
+         .arg(Arg::with_name("MANIFEST")
+              .help("Path to the timeline manifest")
+              .required(true)))
     .get_matches();
 
   if options.subcommand_matches("bootstrap").is_some() {
@@ -25,9 +34,41 @@ fn main() {
       println!("--> {:?}", resource.0);
       copy_file(resource);
     }
+  } else if let Some(matches) = options.subcommand_matches("validate") {
+    let path = matches.value_of("MANIFEST").unwrap();
+
+    if !validate(path) {
+      exit(1);
+    }
   }
 }
 
+// Validate a timeline manifest and print its issues on stderr. Return true if no issue was found.
+fn validate(path: &str) -> bool {
+  let manifest: TimelineManifest = match File::open(path).map_err(|e| format!("{}", e))
+                                                         .and_then(|file| serde_json::from_reader(file).map_err(|e| format!("{}", e))) {
+    Ok(manifest) => manifest,
+    Err(e) => {
+      eprintln!("cannot read {}: {}", path, e);
+      return false;
+    }
+  };
+
+  let issues = manifest.validate();
+
+  if issues.is_empty() {
+    println!("{}: no issue", path);
+    return true;
+  }
+
+  for issue in &issues {
+    eprintln!("--> {}", issue);
+  }
+
+  eprintln!("{}: {} issue(s)", path, issues.len());
+  false
+}
+
 fn copy_file(entry: &(PathBuf, &'static [u8])) {
   let path = entry.0.as_path();
   let parent = path.parent().unwrap_or(&Path::new("."));
@@ -0,0 +1,377 @@
+//! Undoable timeline editing.
+//!
+//! Editing a timeline is done through `Command`s applied to a `TimelineManifest`. Applying a
+//! command returns its inverse, so that it can be undone. A `TimelineEditor` owns a manifest along
+//! with the undo and redo stacks, and can save the edited manifest back to disk. Once edited, the
+//! manifest can be turned into a `Timeline` as usual – or picked up by a `LiveTimeline`.
+//!
+//! Tracks, cuts and overlaps are addressed by their indices in the manifest.
+
+use anim::edit::{ClipRegistry, CutManifest, OverlapManifest, Time, Timeline, TimelineError,
+                 TimelineManifest, TimelineManifestKey, TrackManifest};
+use anim::tempo::{ManifestTime, TempoMap};
+use sys::resource::{Save, SaveError, Store};
+
+/// An invertible edit of a `TimelineManifest`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+  /// Insert a cut in a track at the given index.
+  AddCut { track: usize, index: usize, cut: CutManifest },
+  /// Remove a cut from a track.
+  RemoveCut { track: usize, index: usize },
+  /// Change the instance time of a cut.
+  MoveCut { track: usize, index: usize, inst_time: ManifestTime },
+  /// Change the input and output times of a cut.
+  TrimCut { track: usize, index: usize, in_time: ManifestTime, out_time: ManifestTime },
+  /// Split a cut in two at a given time of the track, in seconds. The second part is inserted right
+  /// after the first one.
+  ///
+  /// The new times are written like the ones they replace: in bars and beats if those are.
+  SplitCut { track: usize, index: usize, at: Time },
+  /// Replace a cut.
+  SetCut { track: usize, index: usize, cut: CutManifest },
+  /// Insert a track at the given index.
+  AddTrack { index: usize, track: TrackManifest },
+  /// Remove a track along with all its cuts.
+  RemoveTrack { index: usize },
+  /// Insert an overlap at the given index.
+  AddOverlap { index: usize, overlap: OverlapManifest },
+  /// Remove an overlap.
+  RemoveOverlap { index: usize },
+  /// Replace an overlap.
+  EditOverlap { index: usize, overlap: OverlapManifest },
+  /// Several commands applied in sequence, and undone as a whole.
+  Batch(Vec<Command>)
+}
+
+/// Error that might occur while applying a `Command`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+  /// No track at this index.
+  NoSuchTrack(usize),
+  /// No cut at this index in this track, as `(track, index)`.
+  NoSuchCut(usize, usize),
+  /// No overlap at this index.
+  NoSuchOverlap(usize),
+  /// The cut would have an input time greater than its output time, both in seconds.
+  InvertedCut(Time, Time),
+  /// The cut cannot be split at this time, either because the time is not strictly inside the cut
+  /// or because the cut loops or has an explicit duration.
+  CannotSplit(Time)
+}
+
+impl Command {
+  /// Apply the command to a manifest and return its inverse.
+  ///
+  /// If the command fails, the manifest is left untouched.
+  pub fn apply(self, manifest: &mut TimelineManifest) -> Result<Command, CommandError> {
+    self.run(manifest, true)
+  }
+
+  /// Apply the command to a manifest without checking the cuts it writes, and return its inverse.
+  ///
+  /// This is how inverses are applied when undoing and redoing: they restore a previous state of the
+  /// manifest as is, even if it was invalid. Addressing a track, cut or overlap that doesn’t exist
+  /// still fails, in which case the manifest is left untouched.
+  pub fn restore(self, manifest: &mut TimelineManifest) -> Result<Command, CommandError> {
+    self.run(manifest, false)
+  }
+
+  fn run(self, manifest: &mut TimelineManifest, check: bool) -> Result<Command, CommandError> {
+    match self {
+      Command::AddCut { track, index, cut } => {
+        if check {
+          check_cut(&cut, &manifest.tempo)?;
+        }
+
+        let cuts = &mut track_mut(manifest, track)?.cuts;
+
+        if index > cuts.len() {
+          return Err(CommandError::NoSuchCut(track, index));
+        }
+
+        cuts.insert(index, cut);
+        Ok(Command::RemoveCut { track: track, index: index })
+      },
+
+      Command::RemoveCut { track, index } => {
+        cut_mut(manifest, track, index)?;
+        let cut = track_mut(manifest, track)?.cuts.remove(index);
+
+        Ok(Command::AddCut { track: track, index: index, cut: cut })
+      },
+
+      Command::MoveCut { track, index, inst_time } => {
+        let cut = cut_mut(manifest, track, index)?;
+        let old = cut.inst_time;
+        cut.inst_time = inst_time;
+
+        Ok(Command::MoveCut { track: track, index: index, inst_time: old })
+      },
+
+      Command::TrimCut { track, index, in_time, out_time } => {
+        let (in_secs, out_secs) = (manifest.tempo.seconds(in_time), manifest.tempo.seconds(out_time));
+
+        if check && in_secs > out_secs {
+          return Err(CommandError::InvertedCut(in_secs, out_secs));
+        }
+
+        let cut = cut_mut(manifest, track, index)?;
+        let old = (cut.in_time, cut.out_time);
+        cut.in_time = in_time;
+        cut.out_time = out_time;
+
+        Ok(Command::TrimCut { track: track, index: index, in_time: old.0, out_time: old.1 })
+      },
+
+      Command::SplitCut { track, index, at } => {
+        let original = cut_mut(manifest, track, index)?.clone();
+        let (first, second) = split(&original, &manifest.tempo, at)?;
+        let cuts = &mut track_mut(manifest, track)?.cuts;
+
+        cuts[index] = first;
+        cuts.insert(index + 1, second);
+
+        Ok(Command::Batch(vec![
+          Command::RemoveCut { track: track, index: index + 1 },
+          Command::SetCut { track: track, index: index, cut: original }
+        ]))
+      },
+
+      Command::SetCut { track, index, cut } => {
+        if check {
+          check_cut(&cut, &manifest.tempo)?;
+        }
+
+        let old = cut_mut(manifest, track, index)?;
+        let old_cut = old.clone();
+        *old = cut;
+
+        Ok(Command::SetCut { track: track, index: index, cut: old_cut })
+      },
+
+      Command::AddTrack { index, track } => {
+        if index > manifest.tracks.len() {
+          return Err(CommandError::NoSuchTrack(index));
+        }
+
+        manifest.tracks.insert(index, track);
+        Ok(Command::RemoveTrack { index: index })
+      },
+
+      Command::RemoveTrack { index } => {
+        track_mut(manifest, index)?;
+        let track = manifest.tracks.remove(index);
+
+        Ok(Command::AddTrack { index: index, track: track })
+      },
+
+      Command::AddOverlap { index, overlap } => {
+        if index > manifest.overlaps.len() {
+          return Err(CommandError::NoSuchOverlap(index));
+        }
+
+        manifest.overlaps.insert(index, overlap);
+        Ok(Command::RemoveOverlap { index: index })
+      },
+
+      Command::RemoveOverlap { index } => {
+        overlap_mut(manifest, index)?;
+        let overlap = manifest.overlaps.remove(index);
+
+        Ok(Command::AddOverlap { index: index, overlap: overlap })
+      },
+
+      Command::EditOverlap { index, overlap } => {
+        let old = overlap_mut(manifest, index)?;
+        let old_overlap = old.clone();
+        *old = overlap;
+
+        Ok(Command::EditOverlap { index: index, overlap: old_overlap })
+      },
+
+      Command::Batch(commands) => {
+        // restored as a whole if a command fails, so that rolling back cannot fail halfway
+        let snapshot = manifest.clone();
+        let mut inverses = Vec::with_capacity(commands.len());
+
+        for command in commands {
+          match command.run(manifest, check) {
+            Ok(inverse) => inverses.push(inverse),
+            Err(e) => {
+              *manifest = snapshot;
+              return Err(e);
+            }
+          }
+        }
+
+        inverses.reverse();
+        Ok(Command::Batch(inverses))
+      }
+    }
+  }
+}
+
+fn track_mut(manifest: &mut TimelineManifest, track: usize) -> Result<&mut TrackManifest, CommandError> {
+  manifest.tracks.get_mut(track).ok_or(CommandError::NoSuchTrack(track))
+}
+
+fn cut_mut(manifest: &mut TimelineManifest, track: usize, index: usize) -> Result<&mut CutManifest, CommandError> {
+  track_mut(manifest, track)?.cuts.get_mut(index).ok_or(CommandError::NoSuchCut(track, index))
+}
+
+fn overlap_mut(manifest: &mut TimelineManifest, index: usize) -> Result<&mut OverlapManifest, CommandError> {
+  manifest.overlaps.get_mut(index).ok_or(CommandError::NoSuchOverlap(index))
+}
+
+fn check_cut(cut: &CutManifest, tempo: &TempoMap) -> Result<(), CommandError> {
+  let (in_time, out_time) = cut.slice(tempo);
+
+  if in_time > out_time {
+    Err(CommandError::InvertedCut(in_time, out_time))
+  } else {
+    Ok(())
+  }
+}
+
+// Split a cut in two parts at the track time at.
+fn split(cut: &CutManifest, tempo: &TempoMap, at: Time) -> Result<(CutManifest, CutManifest), CommandError> {
+  let mapping = cut.time_mapping;
+
+  if mapping.looping || mapping.dur.is_some() {
+    return Err(CommandError::CannotSplit(at));
+  }
+
+  let (in_time, out_time) = cut.slice(tempo);
+  // length of the slice played before the split
+  let elapsed = (at - tempo.seconds(cut.inst_time)) * mapping.speed.abs();
+
+  if !(elapsed > 0. && elapsed < out_time - in_time) {
+    return Err(CommandError::CannotSplit(at));
+  }
+
+  // a reversed cut plays the end of its slice first
+  let reversed = mapping.reverse != (mapping.speed < 0.);
+  let (first, second) = if reversed {
+    let split = out_time - elapsed;
+    ((tempo.to_manifest_time(split, cut.in_time), cut.out_time), (cut.in_time, tempo.to_manifest_time(split, cut.out_time)))
+  } else {
+    let split = in_time + elapsed;
+    ((cut.in_time, tempo.to_manifest_time(split, cut.out_time)), (tempo.to_manifest_time(split, cut.in_time), cut.out_time))
+  };
+
+  let first = CutManifest {
+    in_time: first.0,
+    out_time: first.1,
+    ..cut.clone()
+  };
+
+  let second = CutManifest {
+    in_time: second.0,
+    out_time: second.1,
+    inst_time: tempo.to_manifest_time(at, cut.inst_time),
+    ..cut.clone()
+  };
+
+  Ok((first, second))
+}
+
+/// An editor holding a `TimelineManifest` and its edition history.
+pub struct TimelineEditor {
+  manifest: TimelineManifest,
+  // inverses of the applied commands
+  undo_stack: Vec<Command>,
+  // inverses of the undone commands
+  redo_stack: Vec<Command>
+}
+
+impl TimelineEditor {
+  pub fn new(manifest: TimelineManifest) -> Self {
+    TimelineEditor {
+      manifest: manifest,
+      undo_stack: Vec::new(),
+      redo_stack: Vec::new()
+    }
+  }
+
+  /// Edited manifest.
+  pub fn manifest(&self) -> &TimelineManifest {
+    &self.manifest
+  }
+
+  pub fn into_manifest(self) -> TimelineManifest {
+    self.manifest
+  }
+
+  /// Apply a command and record it in the history. The redo history is discarded.
+  pub fn execute(&mut self, command: Command) -> Result<(), CommandError> {
+    let inverse = command.apply(&mut self.manifest)?;
+
+    self.undo_stack.push(inverse);
+    self.redo_stack.clear();
+
+    Ok(())
+  }
+
+  /// Undo the last command, restoring the manifest as it was before; see `Command::restore`.
+  ///
+  /// Return `Ok(false)` if there was nothing to undo. If undoing fails, the manifest and the
+  /// history are left untouched.
+  pub fn undo(&mut self) -> Result<bool, CommandError> {
+    match self.undo_stack.pop() {
+      Some(inverse) => {
+        match inverse.clone().restore(&mut self.manifest) {
+          Ok(command) => {
+            self.redo_stack.push(command);
+            Ok(true)
+          },
+          Err(e) => {
+            self.undo_stack.push(inverse);
+            Err(e)
+          }
+        }
+      },
+      None => Ok(false)
+    }
+  }
+
+  /// Redo the last undone command, restoring the manifest as it was before undoing it.
+  ///
+  /// Return `Ok(false)` if there was nothing to redo. If redoing fails, the manifest and the
+  /// history are left untouched.
+  pub fn redo(&mut self) -> Result<bool, CommandError> {
+    match self.redo_stack.pop() {
+      Some(command) => {
+        match command.clone().restore(&mut self.manifest) {
+          Ok(inverse) => {
+            self.undo_stack.push(inverse);
+            Ok(true)
+          },
+          Err(e) => {
+            self.redo_stack.push(command);
+            Err(e)
+          }
+        }
+      },
+      None => Ok(false)
+    }
+  }
+
+  pub fn can_undo(&self) -> bool {
+    !self.undo_stack.is_empty()
+  }
+
+  pub fn can_redo(&self) -> bool {
+    !self.redo_stack.is_empty()
+  }
+
+  /// Build a timeline out of the edited manifest; nested timelines are loaded through the store.
+  pub fn timeline<A>(&self, registry: &ClipRegistry<A>, store: &mut Store) -> Result<Timeline<A>, TimelineError> where A: 'static {
+    Timeline::from_registry(&self.manifest, registry, store)
+  }
+
+  /// Save the edited manifest.
+  pub fn save(&self, key: &TimelineManifestKey) -> Result<(), SaveError> {
+    self.manifest.save(key)
+  }
+}
@@ -0,0 +1,130 @@
+//! Easing functions.
+//!
+//! Easing functions remap a normalized time (`[0;1]`) so that the motion between two keys can
+//! accelerate, decelerate, overshoot or bounce. They’re used by `Interpolation::Ease` in splines
+//! but can also be used on their own.
+//!
+//! Every easing kind is defined by its *in* curve. The *out* and *in-out* curves are derived from
+//! it:
+//!
+//! - *out*: `1 - in(1 - t)`
+//! - *in-out*: `in(2t) / 2` on the first half and `1 - in(2 - 2t) / 2` on the second half
+//!
+//! Some easings (`Back` and `Elastic`) go outside of `[0;1]`; that’s expected and gives the
+//! overshooting effect.
+
+use std::f32::consts;
+
+use anim::spline::Time;
+
+/// Overshoot amount used by `EasingKind::Back`.
+const BACK_OVERSHOOT: f32 = 1.70158;
+
+/// Kind of easing.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum EasingKind {
+  /// Quadratic easing (`t²`).
+  #[serde(rename = "quad")]
+  Quad,
+  /// Cubic easing (`t³`).
+  #[serde(rename = "cubic")]
+  Cubic,
+  /// Quartic easing (`t⁴`).
+  #[serde(rename = "quart")]
+  Quart,
+  /// Quintic easing (`t⁵`).
+  #[serde(rename = "quint")]
+  Quint,
+  /// Sinusoidal easing.
+  #[serde(rename = "sine")]
+  Sine,
+  /// Exponential easing.
+  #[serde(rename = "expo")]
+  Expo,
+  /// Circular easing.
+  #[serde(rename = "circ")]
+  Circ,
+  /// Easing that slightly goes back before moving forward.
+  #[serde(rename = "back")]
+  Back,
+  /// Elastic easing, oscillating around the target like a spring.
+  #[serde(rename = "elastic")]
+  Elastic,
+  /// Bouncing easing.
+  #[serde(rename = "bounce")]
+  Bounce
+}
+
+/// Direction in which an easing is applied.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum EasingDirection {
+  /// Easing at the beginning of the segment.
+  #[serde(rename = "in")]
+  In,
+  /// Easing at the end of the segment.
+  #[serde(rename = "out")]
+  Out,
+  /// Easing at both the beginning and the end of the segment.
+  #[serde(rename = "in_out")]
+  InOut
+}
+
+impl EasingKind {
+  /// Apply the easing in the given direction to a normalized time.
+  pub fn ease(self, dir: EasingDirection, t: Time) -> Time {
+    match dir {
+      EasingDirection::In => self.ease_in(t),
+      EasingDirection::Out => 1. - self.ease_in(1. - t),
+      EasingDirection::InOut => {
+        if t < 0.5 {
+          self.ease_in(2. * t) * 0.5
+        } else {
+          1. - self.ease_in(2. - 2. * t) * 0.5
+        }
+      }
+    }
+  }
+
+  // The “in” curve of the easing, from which all other directions are derived.
+  fn ease_in(self, t: Time) -> Time {
+    match self {
+      EasingKind::Quad => t * t,
+      EasingKind::Cubic => t * t * t,
+      EasingKind::Quart => t * t * t * t,
+      EasingKind::Quint => t * t * t * t * t,
+      EasingKind::Sine => 1. - f32::cos(t * consts::FRAC_PI_2),
+      EasingKind::Expo => if t <= 0. { 0. } else { f32::powf(2., 10. * t - 10.) },
+      EasingKind::Circ => 1. - f32::sqrt(1. - t * t),
+      EasingKind::Back => (BACK_OVERSHOOT + 1.) * t * t * t - BACK_OVERSHOOT * t * t,
+      EasingKind::Elastic => {
+        if t <= 0. {
+          0.
+        } else if t >= 1. {
+          1.
+        } else {
+          -f32::powf(2., 10. * t - 10.) * f32::sin((10. * t - 10.75) * 2. * consts::PI / 3.)
+        }
+      },
+      EasingKind::Bounce => 1. - bounce_out(1. - t)
+    }
+  }
+}
+
+// Bouncing curve, which is naturally expressed as an “out” easing.
+fn bounce_out(t: Time) -> Time {
+  let n = 7.5625;
+  let d = 2.75;
+
+  if t < 1. / d {
+    n * t * t
+  } else if t < 2. / d {
+    let t = t - 1.5 / d;
+    n * t * t + 0.75
+  } else if t < 2.5 / d {
+    let t = t - 2.25 / d;
+    n * t * t + 0.9375
+  } else {
+    let t = t - 2.625 / d;
+    n * t * t + 0.984375
+  }
+}
@@ -1,155 +1,913 @@
-use serde_json::from_reader;
-use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value, from_reader, from_value, to_writer_pretty};
+use std::any::Any;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::path::PathBuf;
+use std::rc::Rc;
 
-use sys::resource::{CacheKey, Load, LoadError, LoadResult, Store, StoreKey};
+use anim::interval::IntervalIndex;
+use anim::spline::{Key, Spline};
+use anim::tempo::{BeatInfo, ManifestTime, TempoMap};
+use sys::resource::{CacheKey, Load, LoadError, LoadResult, Res, Save, SaveError, Store, StoreKey};
 
 /// Time.
 pub type Time = f64;
 
-pub struct Clip<'a, A> where A: 'a {
-  gen_node: Box<Fn(Time) -> A + 'a>
+/// A clip is a named generator of values over time. It’s sliced and scheduled by `Cut`s.
+///
+/// The name of the clip is the one used in `TimelineManifest`s to refer to it. Clips are shared
+/// between the cuts that use them, hence they’re handled through `Rc`s.
+///
+/// A clip can be parameterized: each cut then passes its own parameters to the clip, so that the
+/// same clip can be reused with different settings.
+pub struct Clip<A> {
+  name: String,
+  // deserialize the parameters of a cut
+  parse_params: Box<Fn(&Value) -> Result<Rc<Any>, String>>,
+  // None if the clip has nothing to play at the given time
+  gen_node: Box<Fn(Time, &Any) -> Option<A>>
 }
 
-impl<'a, A> Clip<'a, A> {
-  pub fn new<F>(f: F) -> Self where F: 'a + Fn(Time) -> A {
+impl<A> Clip<A> where A: 'static {
+  pub fn new<F>(name: &str, f: F) -> Self where F: 'static + Fn(Time) -> A {
     Clip {
-      gen_node: Box::new(f)
+      name: name.to_owned(),
+      parse_params: Box::new(no_params),
+      gen_node: Box::new(move |t, _| Some(f(t)))
+    }
+  }
+
+  /// Create a clip parameterized by its cuts.
+  ///
+  /// The parameters of a cut are deserialized into `P` once, when the cut is created, and passed to
+  /// the clip along with the time. A cut without parameters deserializes them from an empty object,
+  /// so that `P` can provide defaults with `#[serde(default)]`.
+  pub fn with_params<P, F>(name: &str, f: F) -> Self where P: 'static + DeserializeOwned, F: 'static + Fn(Time, &P) -> A {
+    Clip {
+      name: name.to_owned(),
+      parse_params: Box::new(|params| {
+        let params = if params.is_null() { Value::Object(Map::new()) } else { params.clone() };
+        from_value::<P>(params).map(|p| Rc::new(p) as Rc<Any>).map_err(|e| format!("{}", e))
+      }),
+      gen_node: Box::new(move |t, params| params.downcast_ref::<P>().map(|p| f(t, p)))
+    }
+  }
+
+  /// Create a clip out of a whole timeline.
+  ///
+  /// The time the clip receives is used to play the nested timeline, so that it can be sliced and
+  /// transformed with a `TimeMapping` like any other clip. When the nested timeline has nothing to
+  /// play – or cannot resolve several active cuts – the clip is considered inactive.
+  pub fn from_timeline(name: &str, timeline: Timeline<A>) -> Self {
+    Clip {
+      name: name.to_owned(),
+      parse_params: Box::new(no_params),
+      gen_node: Box::new(move |t, _| {
+        match timeline.play(t) {
+          Played::Resolved(a) => Some(a),
+          _ => None
+        }
+      })
     }
   }
 }
 
+fn no_params(_: &Value) -> Result<Rc<Any>, String> {
+  Ok(Rc::new(()) as Rc<Any>)
+}
+
+impl<A> Clip<A> {
+  /// Name of the clip.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+}
+
 /// A cut is an object that slices a `Clip` at an *input time* and *output time*. It is instantiated
 /// in a `Track` at a given *instance time*.
+///
+/// When played, the clip receives its *local time*: the time elapsed since the instance time, offset
+/// by the input time and transformed by the cut’s `TimeMapping`, along with the cut’s parameters.
 #[derive(Clone)]
-pub struct Cut<'a, 'b, A> where A: 'a, 'a: 'b {
+pub struct Cut<A> {
   pub in_time: Time,
   pub out_time: Time,
   pub inst_time: Time,
-  pub clip: &'b Clip<'a, A>
+  pub time_mapping: TimeMapping,
+  pub clip: Rc<Clip<A>>,
+  params: Value,
+  parsed_params: Rc<Any>,
+  // input, output and instance times as written in the manifest, exported back while they match
+  written: (ManifestTime, ManifestTime, ManifestTime)
 }
 
-impl<'a, 'b, A> Cut<'a, 'b, A> where A :'a, 'a: 'b {
-  pub fn new(in_time: Time, out_time: Time, inst_time: Time, clip: &'b Clip<'a, A>) -> Self {
-    assert!(in_time <= out_time);
+impl<A> Cut<A> {
+  /// Create a cut without parameters.
+  ///
+  /// Fail with `TimelineError::InvertedCut` if the input time is greater than the output time, and
+  /// with `TimelineError::InvalidParams` if the clip requires parameters; use `Cut::with_params`
+  /// for such clips.
+  pub fn new(in_time: Time, out_time: Time, inst_time: Time, clip: Rc<Clip<A>>) -> Result<Self, TimelineError> {
+    Self::with_params(in_time, out_time, inst_time, clip, Value::Null)
+  }
 
-    Cut {
+  /// Create a cut passing parameters to its clip.
+  ///
+  /// Fail with `TimelineError::InvertedCut` if the input time is greater than the output time, and
+  /// with `TimelineError::InvalidParams` if the parameters cannot be deserialized into the clip’s
+  /// parameters type.
+  pub fn with_params(in_time: Time, out_time: Time, inst_time: Time, clip: Rc<Clip<A>>, params: Value) -> Result<Self, TimelineError> {
+    if in_time > out_time {
+      return Err(TimelineError::InvertedCut(clip.name.clone()));
+    }
+
+    let parsed_params = (clip.parse_params)(&params).map_err(|e| TimelineError::InvalidParams(clip.name.clone(), e))?;
+
+    Ok(Cut {
       in_time: in_time,
       out_time: out_time,
       inst_time: inst_time,
-      clip: clip
+      time_mapping: TimeMapping::default(),
+      clip: clip,
+      params: params,
+      parsed_params: parsed_params,
+      written: (in_time.into(), out_time.into(), inst_time.into())
+    })
+  }
+
+  /// Parameters of the cut, as found in the manifest.
+  pub fn params(&self) -> &Value {
+    &self.params
+  }
+
+  /// Change the time mapping of the cut.
+  pub fn with_time_mapping(self, time_mapping: TimeMapping) -> Self {
+    Cut {
+      time_mapping: time_mapping,
+      ..self
     }
   }
 
-  /// Duration of the cut.
-  pub fn dur(&self) -> Time {
+  /// Length of the slice of the clip.
+  pub fn slice_len(&self) -> Time {
     self.out_time - self.in_time
   }
+
+  /// Duration of the cut in the track.
+  ///
+  /// Unless explicitly set in the time mapping, it’s the length of the slice at the cut’s speed.
+  pub fn dur(&self) -> Time {
+    self.time_mapping.track_dur(self.slice_len())
+  }
+
+  /// Play the cut at a time of the track: its clip receives the local time and the cut’s
+  /// parameters. `None` if the clip has nothing to play.
+  pub fn play(&self, t: Time) -> Option<A> {
+    (self.clip.gen_node)(self.local_time(t), &*self.parsed_params)
+  }
+
+  /// Map a time in the track to a time local to the clip.
+  pub fn local_time(&self, t: Time) -> Time {
+    let mapping = &self.time_mapping;
+
+    if let Some(frozen) = mapping.freeze {
+      return frozen;
+    }
+
+    let len = self.slice_len();
+    let elapsed = (t - self.inst_time) * mapping.speed.abs();
+    let elapsed = if mapping.looping && len > 0. {
+      let looped = elapsed % len;
+
+      // the end of the cut shows the end of the slice rather than the start of another loop
+      if looped == 0. && elapsed > 0. && t >= self.inst_time + self.dur() { len } else { looped }
+    } else {
+      elapsed.min(len)
+    };
+
+    // a negative speed reverses the cut as well
+    if mapping.reverse != (mapping.speed < 0.) {
+      self.out_time - elapsed
+    } else {
+      self.in_time + elapsed
+    }
+  }
+}
+
+/// Modifiers applied to the time a cut passes to its clip.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TimeMapping {
+  /// Playback speed; `2` plays twice as fast, `0.5` twice as slow.
+  #[serde(default = "default_speed")]
+  pub speed: Time,
+  /// Play the slice backwards, from the output time to the input time.
+  #[serde(default)]
+  pub reverse: bool,
+  /// Loop inside the slice instead of holding its last frame.
+  ///
+  /// Each loop starts over at the beginning of the slice, but a cut ending right at the end of a
+  /// loop plays the end of the slice on its last instant.
+  #[serde(default, rename = "loop")]
+  pub looping: bool,
+  /// Always pass this (clip-local) time to the clip.
+  #[serde(default)]
+  pub freeze: Option<Time>,
+  /// Duration of the cut in the track, if different from the length of the slice at the cut’s
+  /// speed. Mostly useful with `looping`.
+  #[serde(default)]
+  pub dur: Option<Time>
+}
+
+fn default_speed() -> Time {
+  1.
+}
+
+impl TimeMapping {
+  /// Is this mapping the identity mapping?
+  pub fn is_identity(&self) -> bool {
+    *self == Self::default()
+  }
+
+  /// Duration in a track of a slice of the given length.
+  pub fn track_dur(&self, slice_len: Time) -> Time {
+    self.dur.unwrap_or_else(|| slice_len / self.speed.abs())
+  }
+
+  /// Check that the mapping gives cuts a finite duration: the speed must not be zero and an
+  /// explicit duration must not be negative. The reason is returned otherwise.
+  pub fn check(&self) -> Result<(), String> {
+    if !(self.speed != 0.) {
+      Err(format!("the speed ({}) must not be zero", self.speed))
+    } else {
+      match self.dur {
+        Some(dur) if !(dur >= 0.) => Err(format!("the duration ({}) must not be negative", dur)),
+        _ => Ok(())
+      }
+    }
+  }
+}
+
+impl Default for TimeMapping {
+  fn default() -> Self {
+    TimeMapping {
+      speed: default_speed(),
+      reverse: false,
+      looping: false,
+      freeze: None,
+      dur: None
+    }
+  }
 }
 
 /// A track gathers `Cut`s and its purpose is to be used inside a `Timeline`.
+///
+/// When several tracks are active at the same time, they’re resolved by increasing priority: each
+/// track is blended over the result of the tracks of lower priority with its `BlendMode`. Tracks
+/// with the same priority are resolved in the order they were added to the timeline.
 #[derive(Clone)]
-pub struct Track<'a, 'b, A> where A: 'a, 'a: 'b {
-  cuts: Vec<Cut<'a, 'b, A>>
+pub struct Track<A> {
+  cuts: Vec<Cut<A>>,
+  priority: i32,
+  blend: Option<BlendMode<A>>
 }
 
-impl<'a, 'b, A> Track<'a, 'b, A> where A: 'a, 'a: 'b {
+impl<A> Track<A> {
   pub fn new() -> Self {
     Track {
-      cuts: Vec::new()
+      cuts: Vec::new(),
+      priority: 0,
+      blend: None
     }
   }
 
-  pub fn add_cut(&mut self, cut: Cut<'a, 'b, A>) {
+  /// Change the priority of the track.
+  pub fn with_priority(self, priority: i32) -> Self {
+    Track {
+      priority: priority,
+      ..self
+    }
+  }
+
+  /// Change the blend mode of the track.
+  pub fn with_blend(self, blend: BlendMode<A>) -> Self {
+    Track {
+      blend: Some(blend),
+      ..self
+    }
+  }
+
+  pub fn priority(&self) -> i32 {
+    self.priority
+  }
+
+  /// Blend mode of the track, if any.
+  pub fn blend(&self) -> Option<&BlendMode<A>> {
+    self.blend.as_ref()
+  }
+
+  pub fn add_cut(&mut self, cut: Cut<A>) {
     self.cuts.push(cut);
   }
+
+  /// Cuts of the track.
+  pub fn cuts(&self) -> &[Cut<A>] {
+    &self.cuts
+  }
 }
 
-impl<'a, 'b, 'c, A> From<&'c [Cut<'a, 'b, A>]> for Track<'a, 'b, A> where A: Clone {
-  fn from(cuts: &'c [Cut<'a, 'b, A>]) -> Self {
+impl<'a, A> From<&'a [Cut<A>]> for Track<A> where A: Clone {
+  fn from(cuts: &'a [Cut<A>]) -> Self {
     Track {
-      cuts: cuts.to_vec()
+      cuts: cuts.to_vec(),
+      priority: 0,
+      blend: None
     }
   }
 }
 
+/// How a track is blended over the tracks of lower priority.
+#[derive(Clone)]
+pub enum BlendMode<A> {
+  /// The track replaces whatever is below it.
+  Replace,
+  /// The track is mixed with what is below it by a weight animated over the timeline’s time: `0`
+  /// keeps what is below, `1` is the track only. An empty spline is a weight of `1`; where the
+  /// spline cannot be sampled, the value of the previous key is held.
+  ///
+  /// Mixing is done with the timeline’s mix function; see `Timeline::set_mix`.
+  Crossfade(Spline<f32>),
+  /// The track and what is below it are folded, in that order, with a custom fold.
+  Fold(Rc<Fold<A>>)
+}
+
 /// A timeline gathers tracks used to build up the visual aspect of the demo.
-pub struct Timeline<'a, 'b, A> where A: 'a, 'a: 'b {
-  tracks: Vec<Track<'a, 'b, A>>,
-  overlaps: Vec<Overlap<'a, A>>
+///
+/// Cuts and overlaps are indexed by time the first time the timeline is played after they’re
+/// added, so that finding the ones active at a given time doesn’t require scanning all of them.
+///
+/// A timeline also holds a `TempoMap` so that you can synchronize effects with the music.
+///
+/// A timeline owns its tracks and shares its clips and folds through `Rc`s, so that it can be
+/// stored and rebuilt freely.
+pub struct Timeline<A> {
+  tracks: Vec<Track<A>>,
+  overlaps: Vec<Overlap<A>>,
+  event_tracks: Vec<EventTrack>,
+  // used by crossfading tracks
+  mix: Option<Rc<Fn(A, A, f32) -> A>>,
+  tempo: TempoMap,
+  // references to nested timelines’ manifests, kept for exporting
+  timelines: BTreeMap<String, String>,
+  // (track, cut) pairs, in the order of cut_index’s identifiers
+  cut_ids: Vec<(usize, usize)>,
+  // built lazily, so that adding many tracks or overlaps doesn’t rebuild them every time
+  cut_index: RefCell<Option<IntervalIndex>>,
+  overlap_index: RefCell<Option<IntervalIndex>>
 }
 
-impl<'a, 'b, A> Timeline<'a, 'b, A> where A: 'a, 'a: 'b {
+impl<A> Timeline<A> {
   pub fn new() -> Self {
     Timeline {
       tracks: Vec::new(),
-      overlaps: Vec::new()
+      overlaps: Vec::new(),
+      event_tracks: Vec::new(),
+      mix: None,
+      tempo: TempoMap::default(),
+      timelines: BTreeMap::new(),
+      cut_ids: Vec::new(),
+      cut_index: RefCell::new(None),
+      overlap_index: RefCell::new(None)
     }
   }
 
   /// Turn a TimelineManifest into a Timeline by providing a mapping between clips’ names and real
-  /// clips.
-  pub fn from_manifest(manifest: &TimelineManifest, mapping: &HashMap<String, &'b Clip<'a, A>>) -> Self {
+  /// clips, and a mapping between folds’ names and real folds for the overlaps.
+  ///
+  /// The mappings should use the clips’ and folds’ names as keys, as those are the names used when
+  /// exporting back with `to_manifest`.
+  ///
+  /// No mix function is provided this way, so crossfading tracks fail with
+  /// `TimelineError::MissingMix`; build such timelines with `Timeline::from_registry`.
+  pub fn from_manifest(manifest: &TimelineManifest,
+                       mapping: &HashMap<String, Rc<Clip<A>>>,
+                       folds: &HashMap<String, Rc<Fold<A>>>)
+                       -> Result<Self, TimelineError> {
+    Self::resolve(manifest,
+                  |name| mapping.get(name).cloned(),
+                  |name| folds.get(name).cloned(),
+                  None)
+  }
+
+  /// Turn a TimelineManifest into a Timeline by looking up clips and folds in a registry.
+  ///
+  /// Nested timelines are loaded through the store and built from the same registry, recursively;
+  /// each one is played as a clip named after it, which takes precedence over the registry’s clips.
+  /// A timeline containing itself is rejected with `TimelineError::CyclicTimeline`.
+  ///
+  /// The mix function of the registry is used as the timeline’s one; crossfading tracks fail with
+  /// `TimelineError::MissingMix` if the registry has none.
+  pub fn from_registry(manifest: &TimelineManifest, registry: &ClipRegistry<A>, store: &mut Store) -> Result<Self, TimelineError> where A: 'static {
+    Self::resolve_nested(manifest, registry, store, &mut Vec::new())
+  }
+
+  // Build a timeline along with its nested timelines.
+  //
+  // keys are the keys of the nested manifests being built, to detect cycles.
+  fn resolve_nested(manifest: &TimelineManifest,
+                    registry: &ClipRegistry<A>,
+                    store: &mut Store,
+                    keys: &mut Vec<TimelineManifestKey>)
+                    -> Result<Self, TimelineError> where A: 'static {
+    let mut clips = HashMap::new();
+
+    for (name, key) in manifest.sub_timeline_keys() {
+      if keys.contains(&key) {
+        return Err(TimelineError::CyclicTimeline(key.0));
+      }
+
+      let sub_manifest = store.get(&key).ok_or_else(|| TimelineError::UnknownTimeline(key.0.clone()))?;
+
+      keys.push(key);
+      let sub_timeline = Self::resolve_nested(&sub_manifest.borrow(), registry, store, keys);
+      keys.pop();
+
+      clips.insert(name.to_owned(), Rc::new(Clip::from_timeline(name, sub_timeline?)));
+    }
+
+    Self::resolve(manifest,
+                  |name| clips.get(name).or_else(|| registry.clip(name)).cloned(),
+                  |name| registry.fold(name).cloned(),
+                  registry.mix.clone())
+  }
+
+  fn resolve<C, F>(manifest: &TimelineManifest,
+                   get_clip: C,
+                   get_fold: F,
+                   mix: Option<Rc<Fn(A, A, f32) -> A>>)
+                   -> Result<Self, TimelineError>
+      where C: Fn(&str) -> Option<Rc<Clip<A>>>,
+            F: Fn(&str) -> Option<Rc<Fold<A>>> {
     let mut timeline = Self::new();
+    timeline.mix = mix;
+    let tempo = &manifest.tempo;
+
+    if let Some(change) = tempo.changes().iter().find(|change| !change.is_valid()) {
+      return Err(TimelineError::InvalidTempo(change.bar));
+    }
 
     for track_manifest in &manifest.tracks {
-      let mut track = Track::new();
+      let blend = match track_manifest.blend {
+        Some(BlendManifest::Replace) => Some(BlendMode::Replace),
+        Some(BlendManifest::Crossfade(ref keys)) => {
+          if timeline.mix.is_none() {
+            return Err(TimelineError::MissingMix);
+          }
+
+          Some(BlendMode::Crossfade(Spline::from_keys(keys.clone())))
+        },
+        Some(BlendManifest::Fold(ref name)) => {
+          let fold = get_fold(name).ok_or_else(|| TimelineError::UnknownFold(name.clone()))?;
+          Some(BlendMode::Fold(fold))
+        },
+        None => None
+      };
+
+      let mut track = Track {
+        cuts: Vec::new(),
+        priority: track_manifest.priority,
+        blend: blend
+      };
 
       for cut_manifest in &track_manifest.cuts {
-        let in_time = cut_manifest.in_time;
-        let out_time = cut_manifest.out_time;
-        let inst_time = cut_manifest.inst_time;
-
-        if let Some(clip) = mapping.get(&cut_manifest.clip).cloned() {
-          track.add_cut(Cut::new(in_time, out_time, inst_time, clip));
-        } else {
-          warn!("the clip {:?} doesn’t exist", cut_manifest.clip);
-        }
+        let in_time = tempo.seconds(cut_manifest.in_time);
+        let out_time = tempo.seconds(cut_manifest.out_time);
+        let inst_time = tempo.seconds(cut_manifest.inst_time);
+        let clip = get_clip(&cut_manifest.clip).ok_or_else(|| TimelineError::UnknownClip(cut_manifest.clip.clone()))?;
+
+        cut_manifest.time_mapping.check().map_err(|e| TimelineError::InvalidTimeMapping(cut_manifest.clip.clone(), e))?;
+
+        let mut cut = Cut::with_params(in_time, out_time, inst_time, clip, cut_manifest.params.clone())?.with_time_mapping(cut_manifest.time_mapping);
+        cut.written = (cut_manifest.in_time, cut_manifest.out_time, cut_manifest.inst_time);
+        track.add_cut(cut);
       }
 
       timeline.add_track(track);
     }
 
-    timeline
+    for overlap_manifest in &manifest.overlaps {
+      let fold = get_fold(&overlap_manifest.fold).ok_or_else(|| TimelineError::UnknownFold(overlap_manifest.fold.clone()))?;
+
+      let inst_time = tempo.seconds(overlap_manifest.inst_time);
+      let mut overlap = Overlap::new(inst_time, tempo.duration(inst_time, overlap_manifest.dur), fold);
+      overlap.written = (overlap_manifest.inst_time, overlap_manifest.dur);
+
+      timeline.add_overlap(overlap);
+    }
+
+    for event_track_manifest in &manifest.events {
+      let mut event_track = EventTrack::new();
+
+      for event_manifest in &event_track_manifest.events {
+        let mut event = Event::new(tempo.seconds(event_manifest.time), &event_manifest.name, event_manifest.payload.clone());
+        event.written = event_manifest.time;
+
+        event_track.add_event(event);
+      }
+
+      timeline.add_event_track(event_track);
+    }
+
+    timeline.set_tempo(tempo.clone());
+    timeline.timelines = manifest.timelines.clone();
+
+    Ok(timeline)
   }
 
-  pub fn add_track(&mut self, track: Track<'a, 'b, A>) {
+  /// Export the current state of the timeline as a `TimelineManifest`.
+  ///
+  /// Times of cuts, overlaps and events are exported as written in the manifest the timeline was built
+  /// from, as long as they still resolve to the same times; other times are exported in seconds.
+  pub fn to_manifest(&self) -> TimelineManifest {
+    let tempo = &self.tempo;
+
+    TimelineManifest {
+      tracks: self.tracks.iter().map(|track| {
+        TrackManifest {
+          priority: track.priority,
+          blend: track.blend.as_ref().map(|blend| {
+            match *blend {
+              BlendMode::Replace => BlendManifest::Replace,
+              BlendMode::Crossfade(ref weight) => BlendManifest::Crossfade(weight.into_iter().cloned().collect()),
+              BlendMode::Fold(ref fold) => BlendManifest::Fold(fold.name().to_owned())
+            }
+          }),
+          cuts: track.cuts.iter().map(|cut| {
+            CutManifest {
+              in_time: as_written(cut.written.0, tempo.seconds(cut.written.0), cut.in_time),
+              out_time: as_written(cut.written.1, tempo.seconds(cut.written.1), cut.out_time),
+              inst_time: as_written(cut.written.2, tempo.seconds(cut.written.2), cut.inst_time),
+              time_mapping: cut.time_mapping,
+              params: cut.params.clone(),
+              clip: cut.clip.name().to_owned()
+            }
+          }).collect()
+        }
+      }).collect(),
+      overlaps: self.overlaps.iter().map(|overlap| {
+        let written_inst_time = tempo.seconds(overlap.written.0);
+
+        OverlapManifest {
+          inst_time: as_written(overlap.written.0, written_inst_time, overlap.inst_time),
+          dur: as_written(overlap.written.1, tempo.duration(written_inst_time, overlap.written.1), overlap.dur),
+          fold: overlap.fold.name().to_owned()
+        }
+      }).collect(),
+      events: self.event_tracks.iter().map(|event_track| {
+        EventTrackManifest {
+          events: event_track.events.iter().map(|event| {
+            EventManifest {
+              time: as_written(event.written, tempo.seconds(event.written), event.time),
+              name: event.name.clone(),
+              payload: event.payload.clone()
+            }
+          }).collect()
+        }
+      }).collect(),
+      timelines: self.timelines.clone(),
+      tempo: self.tempo.clone()
+    }
+  }
+
+  /// Tempo map of the timeline.
+  pub fn tempo(&self) -> &TempoMap {
+    &self.tempo
+  }
+
+  pub fn set_tempo(&mut self, tempo: TempoMap) {
+    self.tempo = tempo;
+  }
+
+  /// Beat, bar and beat phase at a given time.
+  pub fn beat_info(&self, t: Time) -> BeatInfo {
+    self.tempo.beat_info(t)
+  }
+
+  pub fn add_track(&mut self, track: Track<A>) {
+    let track_id = self.tracks.len();
+
+    self.cut_ids.extend((0..track.cuts.len()).map(|cut_id| (track_id, cut_id)));
     self.tracks.push(track);
+    *self.cut_index.borrow_mut() = None;
   }
 
-  pub fn add_overlap(&mut self, overlap: Overlap<'a, A>) {
-    self.overlaps.push(overlap)
+  pub fn add_overlap(&mut self, overlap: Overlap<A>) {
+    self.overlaps.push(overlap);
+    *self.overlap_index.borrow_mut() = None;
   }
 
-  pub fn play(&self, t: Time) -> Played<A> {
-    let mut active_nodes = Vec::new();
+  /// Set the function used to mix crossfading tracks, like `Interpolate::lerp`.
+  pub fn set_mix<F>(&mut self, mix: F) where F: 'static + Fn(A, A, f32) -> A {
+    self.mix = Some(Rc::new(mix));
+  }
 
-    // populate the active nodes
-    for track in &self.tracks {
-      for cut in &track.cuts {
-        if cut.inst_time <= t && t <= cut.inst_time + cut.dur() {
-          active_nodes.push((cut.clip.gen_node)(t));
-        }
-      }
+  pub fn add_event_track(&mut self, event_track: EventTrack) {
+    self.event_tracks.push(event_track);
+  }
+
+  /// Event tracks of the timeline.
+  pub fn event_tracks(&self) -> &[EventTrack] {
+    &self.event_tracks
+  }
+
+  /// Events of all event tracks crossed when going from `prev` to `t`, in the order they’re crossed.
+  ///
+  /// See `EventTrack::crossed` for details.
+  pub fn events_crossed(&self, prev: Time, t: Time) -> Vec<&Event> {
+    self.events_between(prev, t, false)
+  }
+
+  // Events crossed when going from prev to t, including the ones at prev if asked to.
+  fn events_between(&self, prev: Time, t: Time, from_prev: bool) -> Vec<&Event> {
+    let mut events: Vec<_> = self.event_tracks.iter().flat_map(|event_track| event_track.between(prev, t, from_prev)).collect();
+
+    if prev <= t {
+      events.sort_by(|a, b| cmp_time(a.time, b.time));
+    } else {
+      events.sort_by(|a, b| cmp_time(b.time, a.time));
     }
 
-    // apply overlap if needed
+    events
+  }
+
+  /// Play the timeline at the given time.
+  ///
+  /// If several cuts are active and an `Overlap` is active too, the overlap’s fold is applied to all
+  /// of them. Otherwise, the tracks are blended by priority; if a track that must be blended has no
+  /// blend mode – or crossfades without a mix function – or if several cuts of the same track are
+  /// active, `Played::NoOverlap` is returned.
+  ///
+  /// A cut is active from its instance time to its end, both included, but gives way at its end to
+  /// the cuts going on: cuts meeting at an instant – one ending where the next one starts – never
+  /// collide.
+  pub fn play(&self, t: Time) -> Played<A> {
+    let active: Vec<_> = self.active_cuts(t).into_iter().map(|id| {
+      let (track, cut) = self.cut_ids[id];
+      (track, &self.tracks[track].cuts[cut])
+    }).collect();
+    let going_on = active.iter().any(|&(_, cut)| cut.inst_time + cut.dur() > t);
+
+    // populate the active nodes along with their tracks
+    let mut active_nodes: Vec<_> = active.into_iter().filter(|&(_, cut)| !going_on || cut.inst_time + cut.dur() > t).filter_map(|(track, cut)| {
+      cut.play(t).map(|node| (track, node))
+    }).collect();
+
     match active_nodes.len() {
       0 => Played::Inactive,
-      1 => active_nodes.pop().map(Played::Resolved).unwrap_or(Played::Inactive),
+      1 => active_nodes.pop().map(|(_, node)| Played::Resolved(node)).unwrap_or(Played::Inactive),
       _ => {
-        // we need to seek for an overlap here because we have strictly more than one node in hands
-        self.find_overlap(t).map(|overlap| {
-          Played::Resolved((overlap.fold)(active_nodes))
-        }).unwrap_or(Played::NoOverlap)
+        // an overlap overrides the blending of tracks
+        if let Some(overlap) = self.find_overlap(t) {
+          let nodes = active_nodes.into_iter().map(|(_, node)| node).collect();
+          return Played::Resolved((overlap.fold.fold)(nodes));
+        }
+
+        self.blend(active_nodes, t).map(Played::Resolved).unwrap_or(Played::NoOverlap)
       }
     }
   }
 
+  // Blend active nodes by increasing priority of their tracks. A blend mode applies between tracks:
+  // several nodes of the same track collide.
+  fn blend(&self, mut active_nodes: Vec<(usize, A)>, t: Time) -> Option<A> {
+    let tracks = &self.tracks;
+    active_nodes.sort_by_key(|&(track, _)| (tracks[track].priority, track));
+
+    if active_nodes.windows(2).any(|w| w[0].0 == w[1].0) {
+      return None;
+    }
+
+    let mut nodes = active_nodes.into_iter();
+    let mut acc = match nodes.next() {
+      Some((_, node)) => node,
+      None => return None
+    };
+
+    for (track, node) in nodes {
+      acc = match tracks[track].blend {
+        Some(BlendMode::Replace) => node,
+        Some(BlendMode::Crossfade(ref weight)) => {
+          let mix = match self.mix {
+            Some(ref mix) => mix,
+            None => return None
+          };
+          mix(acc, node, crossfade_weight(weight, t))
+        },
+        Some(BlendMode::Fold(ref fold)) => (fold.fold)(vec![acc, node]),
+        None => return None
+      };
+    }
+
+    Some(acc)
+  }
+
   /// Find an active overlap at the given time.
   fn find_overlap(&self, t: Time) -> Option<&Overlap<A>> {
-    self.overlaps.iter().find(|x| x.inst_time <= t && t <= x.inst_time + x.dur)
+    let mut index = self.overlap_index.borrow_mut();
+
+    if index.is_none() {
+      *index = Some(IntervalIndex::new(self.overlaps.iter().map(|overlap| {
+        (overlap.inst_time, overlap.inst_time + overlap.dur)
+      })));
+    }
+
+    index.as_ref().and_then(|index| index.query(t).first().map(|&id| &self.overlaps[id]))
+  }
+
+  // Identifiers of the cuts active at the given time, in the order of cut_ids.
+  fn active_cuts(&self, t: Time) -> Vec<usize> {
+    let mut index = self.cut_index.borrow_mut();
+
+    if index.is_none() {
+      let tracks = &self.tracks;
+
+      *index = Some(IntervalIndex::new(self.cut_ids.iter().map(|&(track, cut)| {
+        let cut = &tracks[track].cuts[cut];
+        (cut.inst_time, cut.inst_time + cut.dur())
+      })));
+    }
+
+    index.as_ref().map_or(Vec::new(), |index| index.query(t))
+  }
+}
+
+/// Error that might occur while resolving a `TimelineManifest`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TimelineError {
+  /// A cut references a clip that doesn’t exist.
+  UnknownClip(String),
+  /// An overlap references a fold that doesn’t exist.
+  UnknownFold(String),
+  /// A cut of the given clip has an input time greater than its output time.
+  InvertedCut(String),
+  /// The parameters of a cut of the given clip are invalid; the reason is given as well.
+  InvalidParams(String, String),
+  /// The time mapping of a cut of the given clip is invalid; the reason is given as well.
+  InvalidTimeMapping(String, String),
+  /// The manifest of a nested timeline couldn’t be loaded; its key is given.
+  UnknownTimeline(String),
+  /// A timeline contains itself, through the nested timeline with the given key.
+  CyclicTimeline(String),
+  /// The tempo change at the given bar doesn’t have a positive BPM or has no beat per bar.
+  InvalidTempo(u32),
+  /// A track crossfades but no mix function was provided.
+  MissingMix
+}
+
+/// A registry of clips and folds, indexed by their names.
+pub struct ClipRegistry<A> {
+  clips: HashMap<String, Rc<Clip<A>>>,
+  folds: HashMap<String, Rc<Fold<A>>>,
+  mix: Option<Rc<Fn(A, A, f32) -> A>>
+}
+
+impl<A> ClipRegistry<A> {
+  pub fn new() -> Self {
+    ClipRegistry {
+      clips: HashMap::new(),
+      folds: HashMap::new(),
+      mix: None
+    }
+  }
+
+  /// Add a clip to the registry. If a clip with the same name already exists, it’s replaced.
+  pub fn add_clip(&mut self, clip: Clip<A>) {
+    self.clips.insert(clip.name.clone(), Rc::new(clip));
+  }
+
+  /// Add a fold to the registry. If a fold with the same name already exists, it’s replaced.
+  pub fn add_fold(&mut self, fold: Fold<A>) {
+    self.folds.insert(fold.name.clone(), Rc::new(fold));
+  }
+
+  /// Set the function used to mix crossfading tracks of the timelines built from this registry.
+  pub fn set_mix<F>(&mut self, mix: F) where F: 'static + Fn(A, A, f32) -> A {
+    self.mix = Some(Rc::new(mix));
+  }
+
+  /// Get a clip by its name.
+  pub fn clip(&self, name: &str) -> Option<&Rc<Clip<A>>> {
+    self.clips.get(name)
+  }
+
+  /// Get a fold by its name.
+  pub fn fold(&self, name: &str) -> Option<&Rc<Fold<A>>> {
+    self.folds.get(name)
+  }
+}
+
+/// A timeline bound to a `TimelineManifest` resource.
+///
+/// A live timeline owns its clips and folds in a `ClipRegistry` and rebuilds itself whenever the
+/// `Store` reloads resources – such as the manifest or the manifest of one of its nested timelines.
+/// If the reloaded manifests cannot be resolved – for instance because they reference an unknown
+/// clip – the error is reported once and the last valid timeline is kept until resources get
+/// reloaded again.
+///
+/// Changes are detected with `Store::generation`, so that synchronizing doesn’t look at the
+/// manifests when nothing was reloaded. Manifests edited in place rather than reloaded require a
+/// call to `invalidate`.
+pub struct LiveTimeline<A> {
+  registry: ClipRegistry<A>,
+  manifest: Res<TimelineManifest>,
+  // store generation the timeline was last synchronized with; None to resolve the manifests again
+  generation: Option<u64>,
+  timeline: Timeline<A>
+}
+
+impl<A> LiveTimeline<A> where A: 'static {
+  /// Bind a registry to a manifest resource. Nested timelines are loaded through the store.
+  pub fn new(registry: ClipRegistry<A>, manifest: Res<TimelineManifest>, store: &mut Store) -> Result<Self, TimelineError> {
+    let timeline = Timeline::from_registry(&manifest.borrow(), &registry, store)?;
+
+    Ok(LiveTimeline {
+      registry: registry,
+      manifest: manifest,
+      generation: Some(store.generation()),
+      timeline: timeline
+    })
+  }
+
+  /// Registry of clips and folds.
+  pub fn registry(&self) -> &ClipRegistry<A> {
+    &self.registry
+  }
+
+  /// Rebuild the timeline if resources were reloaded since the last synchronization, or if it was
+  /// invalidated.
+  ///
+  /// Return `Ok(true)` if the timeline was rebuilt and `Ok(false)` if nothing changed. On error, the
+  /// previous version of the timeline is kept, and the error is only returned once: the manifests
+  /// are not resolved again until resources get reloaded or the timeline is invalidated.
+  pub fn sync(&mut self, store: &mut Store) -> Result<bool, TimelineError> {
+    let generation = store.generation();
+
+    if self.generation == Some(generation) {
+      return Ok(false);
+    }
+
+    self.generation = Some(generation);
+    self.timeline = Timeline::from_registry(&self.manifest.borrow(), &self.registry, store)?;
+
+    Ok(true)
+  }
+
+  /// Resolve the manifests again on the next synchronization.
+  ///
+  /// Use it after editing the manifest – or a nested manifest – in place, as that isn’t seen by the
+  /// store.
+  pub fn invalidate(&mut self) {
+    self.generation = None;
+  }
+
+  /// Get the current timeline.
+  pub fn timeline(&self) -> &Timeline<A> {
+    &self.timeline
+  }
+
+  /// Synchronize with the manifests and play the timeline at the given time.
+  ///
+  /// Resolution errors are reported once and the last valid timeline is played.
+  pub fn play(&mut self, t: Time, store: &mut Store) -> Played<A> {
+    if let Err(e) = self.sync(store) {
+      err!("cannot rebuild the timeline: {:?}", e);
+    }
+
+    self.timeline.play(t)
+  }
+}
+
+// Weight of a crossfade at a given time; `1` if the spline has no key.
+//
+// Where the spline cannot be sampled – outside of its keys, or on the first and last segments of
+// interpolation modes requiring four keys – the value of the key right before the time is held, or
+// the value of the first key before it.
+fn crossfade_weight(weight: &Spline<f32>, t: Time) -> f32 {
+  let t = t as f32;
+
+  weight.sample(t).or_else(|| {
+    weight.into_iter().take_while(|key| key.t <= t).last().or_else(|| weight.into_iter().next()).map(|key| key.value)
+  }).unwrap_or(1.)
+}
+
+// A time as written in a manifest if it resolves to the given time, the time in seconds otherwise.
+fn as_written(written: ManifestTime, resolved: Time, t: Time) -> ManifestTime {
+  if resolved == t {
+    written
+  } else {
+    ManifestTime::Seconds(t)
   }
 }
 
@@ -163,9 +921,147 @@ pub enum Played<A> {
   Inactive
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+/// A one-shot event, fired when the playback crosses its time.
+///
+/// Events are named and carry a free-form JSON payload, so that they can drive anything from a
+/// flash on a snare hit to a scene change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+  pub time: Time,
+  pub name: String,
+  pub payload: Value,
+  // time as written in the manifest, exported back while it matches
+  written: ManifestTime
+}
+
+impl Event {
+  pub fn new(time: Time, name: &str, payload: Value) -> Self {
+    Event {
+      time: time,
+      name: name.to_owned(),
+      payload: payload,
+      written: time.into()
+    }
+  }
+}
+
+/// A track of `Event`s, kept sorted by time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventTrack {
+  events: Vec<Event>
+}
+
+impl EventTrack {
+  pub fn new() -> Self {
+    EventTrack {
+      events: Vec::new()
+    }
+  }
+
+  /// Add an event. Events sharing the same time keep the order in which they were added.
+  pub fn add_event(&mut self, event: Event) {
+    let i = first_event(&self.events, |time| time > event.time);
+    self.events.insert(i, event);
+  }
+
+  /// Events of the track, sorted by time.
+  pub fn events(&self) -> &[Event] {
+    &self.events
+  }
+
+  /// Events crossed when going from `prev` to `t`, in the order they’re crossed.
+  ///
+  /// Going forwards, the events in `]prev; t]` are returned by increasing time; going backwards, the
+  /// events in `[t; prev[` are returned by decreasing time. That way, an event is fired exactly once
+  /// when playing through it, whatever the frame rate, and fired again when scrubbing back over it.
+  pub fn crossed(&self, prev: Time, t: Time) -> Vec<&Event> {
+    self.between(prev, t, false)
+  }
+
+  // Events crossed when going from prev to t, including the ones at prev if asked to.
+  fn between(&self, prev: Time, t: Time, from_prev: bool) -> Vec<&Event> {
+    if prev <= t {
+      let start = first_event(&self.events, |time| time > prev || (from_prev && time == prev));
+      let end = first_event(&self.events, |time| time > t);
+      self.events[start..end].iter().collect()
+    } else {
+      let start = first_event(&self.events, |time| time >= t);
+      let end = first_event(&self.events, |time| time > prev || (!from_prev && time == prev));
+      self.events[start..end].iter().rev().collect()
+    }
+  }
+}
+
+/// A cursor remembering the last time events were queried at.
+///
+/// Use `advance` while playing and `seek` when jumping to another time, so that the events
+/// between the two positions are not fired. The events at the time the cursor was created or
+/// moved to with `seek` are fired by the next `advance`, so that an event at `0` fires when
+/// playing from the start.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EventCursor {
+  time: Time,
+  // the events at time haven’t been fired yet
+  pending: bool
+}
+
+impl EventCursor {
+  pub fn new(t: Time) -> Self {
+    EventCursor {
+      time: t,
+      pending: true
+    }
+  }
+
+  /// Last time the cursor was moved to.
+  pub fn time(&self) -> Time {
+    self.time
+  }
+
+  /// Move the cursor to `t` and return the events crossed on the way.
+  pub fn advance<'a, A>(&mut self, timeline: &'a Timeline<A>, t: Time) -> Vec<&'a Event> {
+    let (prev, pending) = (self.time, self.pending);
+    self.time = t;
+    self.pending = false;
+    timeline.events_between(prev, t, pending)
+  }
+
+  /// Move the cursor to `t` without firing any event; the events at `t` are fired by the next
+  /// `advance`.
+  pub fn seek(&mut self, t: Time) {
+    self.time = t;
+    self.pending = true;
+  }
+}
+
+// Total ordering of times, considering NaN equal to anything, shared by the anim modules.
+pub(crate) fn cmp_time(a: Time, b: Time) -> Ordering {
+  a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+// Index of the first event whose time satisfies a predicate holding for a suffix of the events.
+fn first_event<P>(events: &[Event], pred: P) -> usize where P: Fn(Time) -> bool {
+  events.binary_search_by(|e| if pred(e.time) { Ordering::Greater } else { Ordering::Less }).unwrap_err()
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct TimelineManifest {
-  pub tracks: Vec<TrackManifest>
+  pub tracks: Vec<TrackManifest>,
+  #[serde(default)]
+  pub overlaps: Vec<OverlapManifest>,
+  /// Event tracks.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub events: Vec<EventTrackManifest>,
+  /// Nested timelines, mapping clip names to the keys of their `TimelineManifest`s.
+  ///
+  /// Each nested timeline is played as a clip with the same name. `Timeline::from_registry` loads
+  /// and builds them through the store; with `Timeline::from_manifest`, build them with
+  /// `Clip::from_timeline` and provide them along with the other clips.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub timelines: BTreeMap<String, String>,
+  /// Tempo map used to resolve musical times.
+  #[serde(default, skip_serializing_if = "TempoMap::is_empty")]
+  pub tempo: TempoMap
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -181,6 +1077,13 @@ impl StoreKey for TimelineManifestKey {
   }
 }
 
+impl TimelineManifest {
+  /// Keys of the nested timelines’ manifests, along with the names of the clips they’re played as.
+  pub fn sub_timeline_keys(&self) -> Vec<(&str, TimelineManifestKey)> {
+    self.timelines.iter().map(|(name, key)| (name.as_str(), TimelineManifestKey(key.clone()))).collect()
+  }
+}
+
 impl Load for TimelineManifest {
   type Key = TimelineManifestKey;
 
@@ -194,33 +1097,147 @@ impl Load for TimelineManifest {
   }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+impl Save for TimelineManifest {
+  fn save(&self, key: &Self::Key) -> Result<(), SaveError> {
+    let path = key.key_to_path();
+
+    let file = File::create(&path).map_err(|_| SaveError::CannotWrite(path))?;
+    to_writer_pretty(file, self).map_err(|e| SaveError::SerializationFailed(format!("{:?}", e)))
+  }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct TrackManifest {
-  pub cuts: Vec<CutManifest>
+  pub cuts: Vec<CutManifest>,
+  #[serde(default, skip_serializing_if = "is_zero")]
+  pub priority: i32,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub blend: Option<BlendManifest>
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+impl TrackManifest {
+  pub fn new(cuts: Vec<CutManifest>) -> Self {
+    TrackManifest {
+      cuts: cuts,
+      priority: 0,
+      blend: None
+    }
+  }
+}
+
+fn is_zero(x: &i32) -> bool {
+  *x == 0
+}
+
+/// Blend mode of a track in a `TimelineManifest`.
+///
+/// In JSON, it’s either `"replace"`, `{ "crossfade": [keys] }` with the keys of the weight spline,
+/// or `{ "fold": "name" }`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum BlendManifest {
+  #[serde(rename = "replace")]
+  Replace,
+  #[serde(rename = "crossfade")]
+  Crossfade(Vec<Key<f32>>),
+  #[serde(rename = "fold")]
+  Fold(String)
+}
+
+/// A cut in a `TimelineManifest`.
+///
+/// Times can be written either in seconds or in bars and beats; in the latter case, they’re resolved
+/// with the tempo map of the timeline – input and output times as well, even though they’re local
+/// to the clip.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 pub struct CutManifest {
-  pub in_time: Time,
-  pub out_time: Time,
-  pub inst_time: Time,
+  pub in_time: ManifestTime,
+  pub out_time: ManifestTime,
+  pub inst_time: ManifestTime,
+  #[serde(default, skip_serializing_if = "TimeMapping::is_identity")]
+  pub time_mapping: TimeMapping,
+  /// Parameters passed to the clip.
+  #[serde(default, skip_serializing_if = "Value::is_null")]
+  pub params: Value,
   pub clip: String
 }
 
-/// An overlap is a fold consuming clips’ outputs down to a single one. It’s used whenever two cuts
-/// overlap and need to be merged into a single one. It can be used for styling effect or transitions.
-pub struct Overlap<'a, A> {
+impl CutManifest {
+  /// Input and output times, in seconds.
+  pub fn slice(&self, tempo: &TempoMap) -> (Time, Time) {
+    (tempo.seconds(self.in_time), tempo.seconds(self.out_time))
+  }
+
+  /// Duration of the cut in its track, in seconds.
+  pub fn dur(&self, tempo: &TempoMap) -> Time {
+    let (in_time, out_time) = self.slice(tempo);
+    self.time_mapping.track_dur(out_time - in_time)
+  }
+}
+
+/// An overlap in a `TimelineManifest`.
+///
+/// Its instance time and duration can be written in seconds or in bars and beats; a duration in
+/// bars and beats is counted from the instance time.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct OverlapManifest {
+  pub inst_time: ManifestTime,
+  pub dur: ManifestTime,
+  pub fold: String
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EventTrackManifest {
+  pub events: Vec<EventManifest>
+}
+
+/// An event in a `TimelineManifest`. Its time can be written in seconds or in bars and beats.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct EventManifest {
+  pub time: ManifestTime,
+  pub name: String,
+  #[serde(default, skip_serializing_if = "Value::is_null")]
+  pub payload: Value
+}
+
+/// A fold is a named function consuming clips’ outputs down to a single one.
+///
+/// The name of the fold is the one used in `TimelineManifest`s to refer to it.
+pub struct Fold<A> {
+  name: String,
+  pub fold: Box<Fn(Vec<A>) -> A>
+}
+
+impl<A> Fold<A> {
+  pub fn new<F>(name: &str, f: F) -> Self where F: 'static + Fn(Vec<A>) -> A {
+    Fold {
+      name: name.to_owned(),
+      fold: Box::new(f)
+    }
+  }
+
+  /// Name of the fold.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+}
+
+/// An overlap applies a `Fold` on a time range. It’s used whenever two cuts overlap and need to be
+/// merged into a single one. It can be used for styling effect or transitions.
+pub struct Overlap<A> {
   pub inst_time: Time,
   pub dur: Time,
-  pub fold: Box<Fn(Vec<A>) -> A + 'a>,
+  pub fold: Rc<Fold<A>>,
+  // instance time and duration as written in the manifest, exported back while they match
+  written: (ManifestTime, ManifestTime)
 }
 
-impl<'a, A> Overlap<'a, A> {
-  pub fn new<F>(inst_time: Time, dur: Time, f: F) -> Self where F: 'a + Fn(Vec<A>) -> A {
+impl<A> Overlap<A> {
+  pub fn new(inst_time: Time, dur: Time, fold: Rc<Fold<A>>) -> Self {
     Overlap {
       inst_time: inst_time,
       dur: dur,
-      fold: Box::new(f)
+      fold: fold,
+      written: (inst_time.into(), dur.into())
     }
   }
 }
@@ -0,0 +1,146 @@
+//! Interval index.
+//!
+//! An interval index answers *stabbing queries* – which intervals contain a given point – without
+//! scanning all the intervals. It’s a centered interval tree: each node holds a center and the
+//! intervals containing it, sorted both by start and by end, while the intervals entirely before or
+//! after the center go to the left and right subtrees.
+//!
+//! For *n* intervals, the index is built in *O(n log n)* and takes *O(n)* memory. A query reporting
+//! *m* intervals takes *O(log n + m log m)*, the last term coming from sorting the results.
+//!
+//! Intervals are closed and identified by the order in which they were given; query results are
+//! sorted by that order. Intervals with a start greater than their end – or with NaN bounds – are
+//! never reported.
+
+use std::cmp::Ordering;
+
+use anim::edit::{cmp_time, Time};
+
+/// An index of closed intervals `[start; end]`, answering which ones contain a given time.
+///
+/// The index is immutable: adding intervals requires building it again.
+#[derive(Clone, Debug, Default)]
+pub struct IntervalIndex {
+  intervals: Vec<(Time, Time)>,
+  nodes: Vec<Node>,
+  root: Option<usize>
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+  center: Time,
+  // intervals containing the center, by increasing start
+  by_start: Vec<usize>,
+  // intervals containing the center, by decreasing end
+  by_end: Vec<usize>,
+  // intervals ending before the center
+  left: Option<usize>,
+  // intervals starting after the center
+  right: Option<usize>
+}
+
+impl IntervalIndex {
+  /// Build an index out of closed intervals `[start; end]`.
+  pub fn new<I>(intervals: I) -> Self where I: IntoIterator<Item = (Time, Time)> {
+    let intervals: Vec<_> = intervals.into_iter().collect();
+    let ids = (0..intervals.len()).filter(|&id| intervals[id].0 <= intervals[id].1).collect();
+
+    let mut index = IntervalIndex {
+      intervals: intervals,
+      nodes: Vec::new(),
+      root: None
+    };
+
+    index.root = index.build(ids);
+    index
+  }
+
+  /// Number of intervals in the index, including the ones never reported.
+  pub fn len(&self) -> usize {
+    self.intervals.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.intervals.is_empty()
+  }
+
+  /// Identifiers of the intervals containing `t`, in increasing order.
+  pub fn query(&self, t: Time) -> Vec<usize> {
+    if t.is_nan() {
+      return Vec::new();
+    }
+
+    let mut ids = Vec::new();
+    let mut next = self.root;
+
+    while let Some(node) = next {
+      let node = &self.nodes[node];
+
+      match cmp_time(t, node.center) {
+        Ordering::Less => {
+          ids.extend(node.by_start.iter().cloned().take_while(|&id| self.intervals[id].0 <= t));
+          next = node.left;
+        },
+        Ordering::Greater => {
+          ids.extend(node.by_end.iter().cloned().take_while(|&id| self.intervals[id].1 >= t));
+          next = node.right;
+        },
+        Ordering::Equal => {
+          ids.extend_from_slice(&node.by_start);
+          next = None;
+        }
+      }
+    }
+
+    ids.sort();
+    ids
+  }
+
+  // Build the subtree of the given intervals and return its root.
+  fn build(&mut self, ids: Vec<usize>) -> Option<usize> {
+    if ids.is_empty() {
+      return None;
+    }
+
+    // the median bound is contained by at least one interval, so that every node holds some
+    let mut bounds: Vec<Time> = ids.iter().flat_map(|&id| vec![self.intervals[id].0, self.intervals[id].1]).collect();
+    bounds.sort_by(|a, b| cmp_time(*a, *b));
+    let center = bounds[bounds.len() / 2];
+
+    let mut left = Vec::new();
+    let mut right = Vec::new();
+    let mut by_start = Vec::new();
+
+    for id in ids {
+      let (start, end) = self.intervals[id];
+
+      if end < center {
+        left.push(id);
+      } else if start > center {
+        right.push(id);
+      } else {
+        by_start.push(id);
+      }
+    }
+
+    let mut by_end = by_start.clone();
+    {
+      let intervals = &self.intervals;
+      by_start.sort_by(|&a, &b| cmp_time(intervals[a].0, intervals[b].0));
+      by_end.sort_by(|&a, &b| cmp_time(intervals[b].1, intervals[a].1));
+    }
+
+    let left = self.build(left);
+    let right = self.build(right);
+
+    self.nodes.push(Node {
+      center: center,
+      by_start: by_start,
+      by_end: by_end,
+      left: left,
+      right: right
+    });
+
+    Some(self.nodes.len() - 1)
+  }
+}
@@ -4,7 +4,22 @@
 //! specific, artistic and awesome code.
 //!
 //! While the `edit` module is for general execution and scheduling, the `spline` module is more
-//! about parameterization of a specific value you use with your objects.
+//! about parameterization of a specific value you use with your objects. The `easing` module
+//! provides the easing curves that splines can use between keys. The `rocket` module connects to
+//! the GNU Rocket sync-tracker to edit tracks live. The `noise` module provides procedural
+//! channels that can be layered over splines.
+//!
+//! Timeline manifests can be checked for common mistakes with the `validation` module, and the
+//! `tempo` module maps time to bars and beats so that cuts and effects can follow the music. The
+//! `command` module provides undoable edits of timeline manifests, for building editors. The
+//! `interval` module provides the index timelines use to find the cuts active at a given time.
 
+pub mod command;
+pub mod easing;
 pub mod edit;
+pub mod interval;
+pub mod noise;
+pub mod rocket;
 pub mod spline;
+pub mod tempo;
+pub mod validation;
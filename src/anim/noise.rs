@@ -0,0 +1,352 @@
+//! Procedural noise.
+//!
+//! This module provides seeded Perlin noise in one, two and three dimensions, as well as
+//! `NoiseChannel`, an animation channel driven by fractal noise. Noise channels are sampled with
+//! time, the same way splines are, and are great for camera shakes, flickering lights or any kind
+//! of organic motion. They can be added on top of a `Spline` to perturb hand-made animations.
+//!
+//! A noise channel can be loaded from a JSON file through the `Store`:
+//!
+//! ```ignore
+//! {
+//!   "seed": 42,
+//!   "frequency": 4,
+//!   "amplitude": 0.1,
+//!   "octaves": 3
+//! }
+//! ```
+
+use serde_json::from_reader;
+use std::fmt;
+use std::fs::File;
+use std::hash;
+use std::marker::PhantomData;
+use std::ops::Add;
+use std::path::PathBuf;
+
+use anim::spline::{Interpolate, Spline, Time};
+use linear::{V2, V3, V4};
+use sys::resource::{CacheKey, Load, LoadError, LoadResult, Store, StoreKey};
+
+/// Seeded Perlin noise generator.
+#[derive(Clone)]
+pub struct Perlin {
+  perm: Vec<u8>
+}
+
+impl Perlin {
+  /// Create a new noise generator from a seed.
+  pub fn new(seed: u32) -> Self {
+    let mut table = [0u8; 256];
+
+    for (i, x) in table.iter_mut().enumerate() {
+      *x = i as u8;
+    }
+
+    // shuffle the permutation table with a xorshift generator
+    let mut state = seed ^ 0x9e3779b9;
+    if state == 0 {
+      state = 1;
+    }
+
+    for i in (1..256).rev() {
+      state ^= state << 13;
+      state ^= state >> 17;
+      state ^= state << 5;
+      table.swap(i, state as usize % (i + 1));
+    }
+
+    Perlin {
+      perm: (0..512).map(|i| table[i & 255]).collect()
+    }
+  }
+
+  /// One-dimensional noise, in `[-1;1]`.
+  pub fn noise1(&self, x: f32) -> f32 {
+    let xf = x.floor();
+    let xi = xf as i32 & 255;
+    let x = x - xf;
+    let u = fade(x);
+
+    let a = grad1(self.perm[xi as usize], x);
+    let b = grad1(self.perm[xi as usize + 1], x - 1.);
+
+    lerp(a, b, u) * 2.
+  }
+
+  /// Two-dimensional noise, in `[-1;1]`.
+  pub fn noise2(&self, x: f32, y: f32) -> f32 {
+    let (xf, yf) = (x.floor(), y.floor());
+    let (xi, yi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize);
+    let (x, y) = (x - xf, y - yf);
+    let (u, v) = (fade(x), fade(y));
+    let p = &self.perm;
+
+    let aa = p[p[xi] as usize + yi];
+    let ab = p[p[xi] as usize + yi + 1];
+    let ba = p[p[xi + 1] as usize + yi];
+    let bb = p[p[xi + 1] as usize + yi + 1];
+
+    lerp(lerp(grad2(aa, x, y), grad2(ba, x - 1., y), u),
+         lerp(grad2(ab, x, y - 1.), grad2(bb, x - 1., y - 1.), u),
+         v)
+  }
+
+  /// Three-dimensional noise, in `[-1;1]`.
+  pub fn noise3(&self, x: f32, y: f32, z: f32) -> f32 {
+    let (xf, yf, zf) = (x.floor(), y.floor(), z.floor());
+    let (xi, yi, zi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize, (zf as i32 & 255) as usize);
+    let (x, y, z) = (x - xf, y - yf, z - zf);
+    let (u, v, w) = (fade(x), fade(y), fade(z));
+    let p = &self.perm;
+
+    let a = p[xi] as usize + yi;
+    let aa = p[a] as usize + zi;
+    let ab = p[a + 1] as usize + zi;
+    let b = p[xi + 1] as usize + yi;
+    let ba = p[b] as usize + zi;
+    let bb = p[b + 1] as usize + zi;
+
+    lerp(lerp(lerp(grad3(p[aa], x, y, z), grad3(p[ba], x - 1., y, z), u),
+              lerp(grad3(p[ab], x, y - 1., z), grad3(p[bb], x - 1., y - 1., z), u),
+              v),
+         lerp(lerp(grad3(p[aa + 1], x, y, z - 1.), grad3(p[ba + 1], x - 1., y, z - 1.), u),
+              lerp(grad3(p[ab + 1], x, y - 1., z - 1.), grad3(p[bb + 1], x - 1., y - 1., z - 1.), u),
+              v),
+         w)
+  }
+}
+
+impl fmt::Debug for Perlin {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    f.write_str("Perlin")
+  }
+}
+
+fn fade(t: f32) -> f32 {
+  t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+  a + (b - a) * t
+}
+
+fn grad1(hash: u8, x: f32) -> f32 {
+  if hash & 1 == 0 { x } else { -x }
+}
+
+fn grad2(hash: u8, x: f32, y: f32) -> f32 {
+  match hash & 3 {
+    0 => x + y,
+    1 => -x + y,
+    2 => x - y,
+    _ => -x - y
+  }
+}
+
+fn grad3(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+  let h = hash & 15;
+  let u = if h < 8 { x } else { y };
+  let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+
+  (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Parameters of a noise channel.
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct NoiseParams {
+  /// Seed of the noise.
+  #[serde(default)]
+  pub seed: u32,
+  /// Frequency of the first octave, in oscillations per second.
+  #[serde(default = "default_frequency")]
+  pub frequency: f32,
+  /// Amplitude of the first octave.
+  #[serde(default = "default_amplitude")]
+  pub amplitude: f32,
+  /// Number of octaves to sum up.
+  #[serde(default = "default_octaves")]
+  pub octaves: u32,
+  /// Frequency multiplier applied at each octave.
+  #[serde(default = "default_lacunarity")]
+  pub lacunarity: f32,
+  /// Amplitude multiplier applied at each octave.
+  #[serde(default = "default_persistence")]
+  pub persistence: f32
+}
+
+fn default_frequency() -> f32 { 1. }
+fn default_amplitude() -> f32 { 1. }
+fn default_octaves() -> u32 { 1 }
+fn default_lacunarity() -> f32 { 2. }
+fn default_persistence() -> f32 { 0.5 }
+
+impl Default for NoiseParams {
+  fn default() -> Self {
+    NoiseParams {
+      seed: 0,
+      frequency: default_frequency(),
+      amplitude: default_amplitude(),
+      octaves: default_octaves(),
+      lacunarity: default_lacunarity(),
+      persistence: default_persistence()
+    }
+  }
+}
+
+/// Values that can be generated by a `NoiseChannel`.
+///
+/// Each component of the value is generated from a different, decorrelated slice of the noise.
+pub trait NoiseValue: Copy {
+  /// Build a value by generating each component with `f`, which takes the component index.
+  fn from_components<F>(f: F) -> Self where F: Fn(usize) -> f32;
+}
+
+impl NoiseValue for f32 {
+  fn from_components<F>(f: F) -> Self where F: Fn(usize) -> f32 {
+    f(0)
+  }
+}
+
+impl NoiseValue for V2<f32> {
+  fn from_components<F>(f: F) -> Self where F: Fn(usize) -> f32 {
+    V2::new(f(0), f(1))
+  }
+}
+
+impl NoiseValue for V3<f32> {
+  fn from_components<F>(f: F) -> Self where F: Fn(usize) -> f32 {
+    V3::new(f(0), f(1), f(2))
+  }
+}
+
+impl NoiseValue for V4<f32> {
+  fn from_components<F>(f: F) -> Self where F: Fn(usize) -> f32 {
+    V4::new(f(0), f(1), f(2), f(3))
+  }
+}
+
+/// Animation channel driven by fractal Perlin noise.
+#[derive(Clone, Debug)]
+pub struct NoiseChannel<T> {
+  params: NoiseParams,
+  perlin: Perlin,
+  _t: PhantomData<*const T>
+}
+
+impl<T> NoiseChannel<T> {
+  /// Create a new noise channel.
+  pub fn new(params: NoiseParams) -> Self {
+    NoiseChannel {
+      params: params,
+      perlin: Perlin::new(params.seed),
+      _t: PhantomData
+    }
+  }
+
+  /// Parameters of the channel.
+  pub fn params(&self) -> &NoiseParams {
+    &self.params
+  }
+
+  /// Sample the channel at a given time.
+  pub fn sample(&self, t: Time) -> T where T: NoiseValue {
+    T::from_components(|i| self.fractal(t, i))
+  }
+
+  /// Sample a spline and add the channel on top of it.
+  ///
+  /// Returns `None` if the spline cannot be sampled at this time.
+  pub fn sample_over(&self, spline: &Spline<T>, t: Time) -> Option<T>
+      where T: NoiseValue + Interpolate + Add<Output = T> {
+    spline.sample(t).map(|x| x + self.sample(t))
+  }
+
+  /// Sample a spline with clamping and add the channel on top of it.
+  ///
+  /// # Panic
+  ///
+  /// This function panics if the spline has no key.
+  pub fn clamped_sample_over(&self, spline: &Spline<T>, t: Time) -> T
+      where T: NoiseValue + Interpolate + Add<Output = T> {
+    spline.clamped_sample(t) + self.sample(t)
+  }
+
+  // Fractal sum of octaves for a given component.
+  fn fractal(&self, t: Time, component: usize) -> f32 {
+    let p = &self.params;
+    // each component lives on its own row of the 2D noise
+    let y = component as f32 * 31.416 + 0.5;
+    let mut freq = p.frequency;
+    let mut amp = p.amplitude;
+    let mut r = 0.;
+
+    for _ in 0..p.octaves {
+      r += self.perlin.noise2(t * freq, y) * amp;
+      freq *= p.lacunarity;
+      amp *= p.persistence;
+    }
+
+    r
+  }
+}
+
+/// Key used to load noise channels from the `Store`.
+#[derive(Eq, PartialEq)]
+pub struct NoiseChannelKey<T> {
+  pub key: String,
+  _t: PhantomData<*const T>
+}
+
+impl<T> NoiseChannelKey<T> {
+  pub fn new(key: &str) -> Self {
+    NoiseChannelKey {
+      key: key.to_owned(),
+      _t: PhantomData
+    }
+  }
+}
+
+impl<T> Clone for NoiseChannelKey<T> {
+  fn clone(&self) -> Self {
+    NoiseChannelKey {
+      key: self.key.clone(),
+      ..*self
+    }
+  }
+}
+
+impl<T> fmt::Debug for NoiseChannelKey<T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    self.key.fmt(f)
+  }
+}
+
+impl<T> hash::Hash for NoiseChannelKey<T> {
+  fn hash<H>(&self, hasher: &mut H) where H: hash::Hasher {
+    self.key.hash(hasher)
+  }
+}
+
+impl<T> CacheKey for NoiseChannelKey<T> where T: 'static {
+  type Target = NoiseChannel<T>;
+}
+
+impl<T> StoreKey for NoiseChannelKey<T> where T: 'static {
+  fn key_to_path(&self) -> PathBuf {
+    self.key.clone().into()
+  }
+}
+
+impl<T> Load for NoiseChannel<T> where T: 'static {
+  type Key = NoiseChannelKey<T>;
+
+  fn load(key: &Self::Key, _: &mut Store) -> Result<LoadResult<Self>, LoadError> {
+    let path = key.key_to_path();
+
+    let file = File::open(&path).map_err(|_| LoadError::FileNotFound(path))?;
+    let params: NoiseParams = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    Ok(NoiseChannel::new(params).into())
+  }
+}
@@ -0,0 +1,558 @@
+//! GNU Rocket integration.
+//!
+//! [Rocket](https://github.com/rocket/rocket) is a sync-tracker: an editor in which you edit
+//! *tracks* of keys laid out on *rows* while the demo is running. This module provides a
+//! `SyncDevice` that can be used in two modes:
+//!
+//! - **client** mode: the device connects to a running Rocket editor over TCP, asks for the tracks
+//!   you need, receives key edits live and follows the editor’s pause and seek commands
+//! - **player** mode: the device loads the `.track` files exported by the editor, which is what you
+//!   want in release
+//!
+//! In both modes, tracks are accessed via a `TrackId` and sampled at a given row, the same way
+//! you’d sample a `Spline`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+
+use anim::edit::Time;
+
+/// Default port the Rocket editor listens on.
+pub const DEFAULT_PORT: u16 = 1338;
+
+const CLIENT_GREETING: &'static [u8] = b"hello, synctracker!";
+const SERVER_GREETING: &'static [u8] = b"hello, demo!";
+
+// protocol commands
+const CMD_SET_KEY: u8 = 0;
+const CMD_DELETE_KEY: u8 = 1;
+const CMD_GET_TRACK: u8 = 2;
+const CMD_SET_ROW: u8 = 3;
+const CMD_PAUSE: u8 = 4;
+const CMD_SAVE_TRACKS: u8 = 5;
+
+/// Interpolation used by Rocket between a key and the next one.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum RocketInterpolation {
+  /// Hold the key until the next one.
+  Step,
+  /// Linear interpolation.
+  Linear,
+  /// Smoothstep interpolation.
+  Smooth,
+  /// Quadratic ramp.
+  Ramp
+}
+
+impl RocketInterpolation {
+  fn from_u8(x: u8) -> Option<Self> {
+    match x {
+      0 => Some(RocketInterpolation::Step),
+      1 => Some(RocketInterpolation::Linear),
+      2 => Some(RocketInterpolation::Smooth),
+      3 => Some(RocketInterpolation::Ramp),
+      _ => None
+    }
+  }
+
+  fn to_u8(self) -> u8 {
+    match self {
+      RocketInterpolation::Step => 0,
+      RocketInterpolation::Linear => 1,
+      RocketInterpolation::Smooth => 2,
+      RocketInterpolation::Ramp => 3
+    }
+  }
+}
+
+/// A key in a Rocket track.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RocketKey {
+  /// Row at which the key is set.
+  pub row: u32,
+  /// Value of the key.
+  pub value: f32,
+  /// Interpolation between this key and the next one.
+  pub interpolation: RocketInterpolation
+}
+
+impl RocketKey {
+  pub fn new(row: u32, value: f32, interpolation: RocketInterpolation) -> Self {
+    RocketKey {
+      row: row,
+      value: value,
+      interpolation: interpolation
+    }
+  }
+}
+
+/// A named track of keys.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RocketTrack {
+  name: String,
+  keys: Vec<RocketKey>
+}
+
+impl RocketTrack {
+  /// Create an empty track.
+  pub fn new(name: &str) -> Self {
+    RocketTrack {
+      name: name.to_owned(),
+      keys: Vec::new()
+    }
+  }
+
+  /// Name of the track.
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Keys of the track, sorted by row.
+  pub fn keys(&self) -> &[RocketKey] {
+    &self.keys
+  }
+
+  /// Set a key, replacing the one already at the same row if any.
+  pub fn set_key(&mut self, key: RocketKey) {
+    match self.keys.binary_search_by(|k| k.row.cmp(&key.row)) {
+      Ok(i) => self.keys[i] = key,
+      Err(i) => self.keys.insert(i, key)
+    }
+  }
+
+  /// Delete the key at the given row, if any.
+  pub fn delete_key(&mut self, row: u32) {
+    if let Ok(i) = self.keys.binary_search_by(|k| k.row.cmp(&row)) {
+      self.keys.remove(i);
+    }
+  }
+
+  /// Sample the track at a given (fractional) row.
+  ///
+  /// Before the first key and after the last one, the first and last values are held,
+  /// respectively; a NaN row samples to the first value. An empty track samples to `0`.
+  pub fn sample(&self, row: f64) -> f32 {
+    let keys = &self.keys;
+
+    if keys.is_empty() {
+      return 0.;
+    } else if row.is_nan() {
+      return keys[0].value;
+    }
+
+    // index of the first key strictly after row
+    let i = match keys.binary_search_by(|k| (k.row as f64).partial_cmp(&row).unwrap()) {
+      Ok(i) => return keys[i].value,
+      Err(i) => i
+    };
+
+    if i == 0 {
+      return keys[0].value;
+    } else if i == keys.len() {
+      return keys[i - 1].value;
+    }
+
+    let k0 = &keys[i - 1];
+    let k1 = &keys[i];
+    let t = ((row - k0.row as f64) / (k1.row - k0.row) as f64) as f32;
+    let t = match k0.interpolation {
+      RocketInterpolation::Step => 0.,
+      RocketInterpolation::Linear => t,
+      RocketInterpolation::Smooth => t * t * (3. - 2. * t),
+      RocketInterpolation::Ramp => t * t
+    };
+
+    k0.value + (k1.value - k0.value) * t
+  }
+
+  /// Read a track from the binary `.track` format exported by Rocket.
+  pub fn read<R>(name: &str, reader: &mut R) -> Result<Self, RocketError> where R: Read {
+    let mut track = RocketTrack::new(name);
+    let nb_keys = read_u32_le(reader)?;
+
+    for _ in 0..nb_keys {
+      let row = read_u32_le(reader)?;
+      let value = f32::from_bits(read_u32_le(reader)?);
+      let interpolation = read_interpolation(reader)?;
+
+      track.set_key(RocketKey::new(row, value, interpolation));
+    }
+
+    Ok(track)
+  }
+
+  /// Write a track in the binary `.track` format.
+  pub fn write<W>(&self, writer: &mut W) -> Result<(), RocketError> where W: Write {
+    let mut buf = Vec::with_capacity(4 + self.keys.len() * 9);
+
+    push_u32_le(&mut buf, self.keys.len() as u32);
+
+    for key in &self.keys {
+      push_u32_le(&mut buf, key.row);
+      push_u32_le(&mut buf, key.value.to_bits());
+      buf.push(key.interpolation.to_u8());
+    }
+
+    writer.write_all(&buf).map_err(RocketError::IOError)
+  }
+}
+
+/// Handle to a track in a `SyncDevice`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TrackId(usize);
+
+/// Error that might occur while talking to Rocket or loading tracks.
+#[derive(Debug)]
+pub enum RocketError {
+  /// An I/O error occurred.
+  IOError(io::Error),
+  /// The editor didn’t answer with the expected greeting.
+  WrongGreeting,
+  /// The editor sent a command that is not part of the protocol.
+  UnknownCommand(u8),
+  /// The editor referenced a track that was never requested.
+  UnknownTrack(u32),
+  /// A key has an interpolation that is not part of the protocol.
+  UnknownInterpolation(u8),
+  /// The editor closed the connection.
+  Disconnected
+}
+
+/// Event emitted by the editor that the demo should react to.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SyncEvent {
+  /// The editor has paused (`true`) or resumed (`false`) playback.
+  Pause(bool),
+  /// The editor has moved its cursor to the given row; the demo should seek there.
+  Seek(u32),
+  /// The editor has asked to save the tracks.
+  SaveTracks
+}
+
+enum Mode {
+  Client {
+    stream: TcpStream,
+    // received bytes that don’t form a full command yet
+    pending: Vec<u8>,
+    // last row sent to the editor
+    last_row: Option<u32>
+  },
+  Player
+}
+
+/// Rocket sync device.
+///
+/// A sync device gathers tracks and convert time to rows using a *rows per second* value. See the
+/// module documentation for further details.
+pub struct SyncDevice {
+  /// Base path of the `.track` files.
+  base: PathBuf,
+  /// Number of rows per second.
+  rows_per_sec: f64,
+  tracks: Vec<RocketTrack>,
+  names: HashMap<String, usize>,
+  paused: bool,
+  mode: Mode
+}
+
+impl SyncDevice {
+  /// Connect to a Rocket editor.
+  ///
+  /// `base` is the base path of the tracks; it is used when the editor asks to save the tracks.
+  pub fn connect<A, P>(addr: A, base: P, rows_per_sec: f64) -> Result<Self, RocketError>
+      where A: ToSocketAddrs,
+            P: Into<PathBuf> {
+    let mut stream = TcpStream::connect(addr).map_err(RocketError::IOError)?;
+
+    stream.write_all(CLIENT_GREETING).map_err(RocketError::IOError)?;
+
+    let mut greeting = [0; 12];
+    stream.read_exact(&mut greeting).map_err(RocketError::IOError)?;
+
+    if &greeting[..] != SERVER_GREETING {
+      return Err(RocketError::WrongGreeting);
+    }
+
+    let _ = stream.set_nodelay(true);
+    stream.set_nonblocking(true).map_err(RocketError::IOError)?;
+
+    info!("connected to Rocket editor");
+
+    Ok(SyncDevice {
+      base: base.into(),
+      rows_per_sec: rows_per_sec,
+      tracks: Vec::new(),
+      names: HashMap::new(),
+      paused: true,
+      mode: Mode::Client {
+        stream: stream,
+        pending: Vec::new(),
+        last_row: None
+      }
+    })
+  }
+
+  /// Create a device that loads tracks from `.track` files.
+  ///
+  /// A track named `foo` is loaded from `<base>_foo.track`.
+  pub fn player<P>(base: P, rows_per_sec: f64) -> Self where P: Into<PathBuf> {
+    SyncDevice {
+      base: base.into(),
+      rows_per_sec: rows_per_sec,
+      tracks: Vec::new(),
+      names: HashMap::new(),
+      paused: false,
+      mode: Mode::Player
+    }
+  }
+
+  /// Is the device in client mode?
+  pub fn is_client(&self) -> bool {
+    match self.mode {
+      Mode::Client { .. } => true,
+      Mode::Player => false
+    }
+  }
+
+  /// Has the editor paused the playback?
+  ///
+  /// Always `false` in player mode.
+  pub fn is_paused(&self) -> bool {
+    self.paused
+  }
+
+  /// Convert a time into a row.
+  pub fn row(&self, t: Time) -> f64 {
+    t * self.rows_per_sec
+  }
+
+  /// Convert a row into a time.
+  pub fn time(&self, row: u32) -> Time {
+    row as f64 / self.rows_per_sec
+  }
+
+  /// Get a track by its name.
+  ///
+  /// In client mode, the track is requested to the editor and its keys will arrive with the next
+  /// calls to `update`. In player mode, the track is loaded from its `.track` file.
+  pub fn get_track(&mut self, name: &str) -> Result<TrackId, RocketError> {
+    if let Some(&i) = self.names.get(name) {
+      return Ok(TrackId(i));
+    }
+
+    let path = self.track_path(name);
+    let track = match self.mode {
+      Mode::Client { ref mut stream, .. } => {
+        let mut buf = vec![CMD_GET_TRACK];
+        push_u32_be(&mut buf, name.len() as u32);
+        buf.extend_from_slice(name.as_bytes());
+        write_blocking(stream, &buf)?;
+
+        RocketTrack::new(name)
+      },
+      Mode::Player => {
+        let mut file = File::open(&path).map_err(RocketError::IOError)?;
+
+        RocketTrack::read(name, &mut file)?
+      }
+    };
+
+    let i = self.tracks.len();
+    self.tracks.push(track);
+    self.names.insert(name.to_owned(), i);
+
+    Ok(TrackId(i))
+  }
+
+  /// Access a track.
+  pub fn track(&self, id: TrackId) -> &RocketTrack {
+    &self.tracks[id.0]
+  }
+
+  /// Sample a track at a given time.
+  pub fn sample(&self, id: TrackId, t: Time) -> f32 {
+    self.tracks[id.0].sample(self.row(t))
+  }
+
+  /// Synchronize with the editor.
+  ///
+  /// `t` is the current time of the demo. It’s sent to the editor while playing so that its cursor
+  /// follows the demo. All pending commands are processed and the events the demo should react to
+  /// are returned.
+  ///
+  /// In player mode, this function does nothing.
+  pub fn update(&mut self, t: Time) -> Result<Vec<SyncEvent>, RocketError> {
+    let row = self.row(t).max(0.) as u32;
+    let mut events = Vec::new();
+
+    {
+      let tracks = &mut self.tracks;
+      let paused = &mut self.paused;
+
+      let (stream, pending, last_row) = match self.mode {
+        Mode::Client { ref mut stream, ref mut pending, ref mut last_row } => (stream, pending, last_row),
+        Mode::Player => return Ok(events)
+      };
+
+      // read everything available
+      let mut buf = [0; 4096];
+      loop {
+        match stream.read(&mut buf) {
+          Ok(0) => return Err(RocketError::Disconnected),
+          Ok(n) => pending.extend_from_slice(&buf[..n]),
+          Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+          Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+          Err(e) => return Err(RocketError::IOError(e))
+        }
+      }
+
+      // process complete commands
+      let mut consumed = 0;
+      while let Some((len, event)) = process_command(&pending[consumed..], tracks, paused)? {
+        consumed += len;
+
+        if let Some(event) = event {
+          if let SyncEvent::Seek(row) = event {
+            *last_row = Some(row);
+          }
+
+          events.push(event);
+        }
+      }
+      pending.drain(..consumed);
+
+      // tell the editor where we are
+      if !*paused && *last_row != Some(row) {
+        let mut buf = vec![CMD_SET_ROW];
+        push_u32_be(&mut buf, row);
+        write_blocking(stream, &buf)?;
+        *last_row = Some(row);
+      }
+    }
+
+    if events.contains(&SyncEvent::SaveTracks) {
+      self.save_tracks()?;
+    }
+
+    Ok(events)
+  }
+
+  /// Save all the tracks as `.track` files.
+  pub fn save_tracks(&self) -> Result<(), RocketError> {
+    for track in &self.tracks {
+      let path = self.track_path(&track.name);
+      let mut file = File::create(&path).map_err(RocketError::IOError)?;
+
+      track.write(&mut file)?;
+      info!("saved Rocket track {:?}", path);
+    }
+
+    Ok(())
+  }
+
+  fn track_path(&self, name: &str) -> PathBuf {
+    let mut path = self.base.clone().into_os_string();
+    path.push(format!("_{}.track", name));
+    path.into()
+  }
+}
+
+// Try to process a single command from the input. Return the number of bytes consumed along with an
+// optional event if a full command was available.
+fn process_command(input: &[u8],
+                   tracks: &mut [RocketTrack],
+                   paused: &mut bool)
+                   -> Result<Option<(usize, Option<SyncEvent>)>, RocketError> {
+  let cmd = match input.first() {
+    Some(&cmd) => cmd,
+    None => return Ok(None)
+  };
+
+  let payload_len = match cmd {
+    CMD_SET_KEY => 13,
+    CMD_DELETE_KEY => 8,
+    CMD_SET_ROW => 4,
+    CMD_PAUSE => 1,
+    CMD_SAVE_TRACKS => 0,
+    _ => return Err(RocketError::UnknownCommand(cmd))
+  };
+
+  if input.len() < 1 + payload_len {
+    return Ok(None);
+  }
+
+  let payload = &input[1..1 + payload_len];
+  let event = match cmd {
+    CMD_SET_KEY => {
+      let track = get_track_mut(tracks, be_u32(&payload[0..4]))?;
+      let row = be_u32(&payload[4..8]);
+      let value = f32::from_bits(be_u32(&payload[8..12]));
+      let interpolation = RocketInterpolation::from_u8(payload[12]).ok_or(RocketError::UnknownInterpolation(payload[12]))?;
+
+      track.set_key(RocketKey::new(row, value, interpolation));
+      None
+    },
+    CMD_DELETE_KEY => {
+      let track = get_track_mut(tracks, be_u32(&payload[0..4]))?;
+      track.delete_key(be_u32(&payload[4..8]));
+      None
+    },
+    CMD_SET_ROW => Some(SyncEvent::Seek(be_u32(payload))),
+    CMD_PAUSE => {
+      *paused = payload[0] != 0;
+      Some(SyncEvent::Pause(*paused))
+    },
+    _ => Some(SyncEvent::SaveTracks)
+  };
+
+  Ok(Some((1 + payload_len, event)))
+}
+
+fn get_track_mut(tracks: &mut [RocketTrack], i: u32) -> Result<&mut RocketTrack, RocketError> {
+  tracks.get_mut(i as usize).ok_or(RocketError::UnknownTrack(i))
+}
+
+// Write a whole buffer to a non-blocking stream, which is switched to blocking mode meanwhile so
+// that waiting for the socket doesn’t spin.
+fn write_blocking(stream: &mut TcpStream, buf: &[u8]) -> Result<(), RocketError> {
+  stream.set_nonblocking(false).map_err(RocketError::IOError)?;
+  let written = stream.write_all(buf);
+  stream.set_nonblocking(true).map_err(RocketError::IOError)?;
+
+  written.map_err(|e| {
+    if e.kind() == io::ErrorKind::WriteZero {
+      RocketError::Disconnected
+    } else {
+      RocketError::IOError(e)
+    }
+  })
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+  (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+fn push_u32_be(buf: &mut Vec<u8>, x: u32) {
+  buf.extend_from_slice(&[(x >> 24) as u8, (x >> 16) as u8, (x >> 8) as u8, x as u8]);
+}
+
+fn push_u32_le(buf: &mut Vec<u8>, x: u32) {
+  buf.extend_from_slice(&[x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8]);
+}
+
+fn read_u32_le<R>(reader: &mut R) -> Result<u32, RocketError> where R: Read {
+  let mut bytes = [0; 4];
+  reader.read_exact(&mut bytes).map_err(RocketError::IOError)?;
+
+  Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+}
+
+fn read_interpolation<R>(reader: &mut R) -> Result<RocketInterpolation, RocketError> where R: Read {
+  let mut byte = [0; 1];
+  reader.read_exact(&mut byte).map_err(RocketError::IOError)?;
+
+  RocketInterpolation::from_u8(byte[0]).ok_or(RocketError::UnknownInterpolation(byte[0]))
+}
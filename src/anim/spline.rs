@@ -1,6 +1,7 @@
 use cgmath::{BaseFloat, InnerSpace};
+use serde::Serialize;
 use serde::de::DeserializeOwned;
-use serde_json::from_reader;
+use serde_json::{from_reader, to_writer_pretty};
 use std::f32::consts;
 use std::fs::File;
 use std::fmt;
@@ -9,8 +10,9 @@ use std::marker::PhantomData;
 use std::ops::{Add, Div, Mul, Sub};
 use std::path::PathBuf;
 
+pub use anim::easing::{EasingDirection, EasingKind};
 use linear::{Scale, Quat, V2, V3, V4};
-use sys::resource::{CacheKey, Load, LoadError, LoadResult, Store, StoreKey};
+use sys::resource::{CacheKey, Load, LoadError, LoadResult, Save, SaveError, Store, StoreKey};
 
 /// Time used as sampling type in splines.
 pub type Time = f32;
@@ -19,7 +21,7 @@ pub type Time = f32;
 ///
 /// This type associates a value at a given time. It also contains an interpolation object used to
 /// determine how to interpolate values on the segment defined by this key and the next one.
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Key<T> {
   /// Time at which the `Key` should be reached.
   pub t: Time,
@@ -27,7 +29,10 @@ pub struct Key<T> {
   pub value: T,
   /// Interpolation mode.
   #[serde(default)]
-  pub interpolation: Interpolation
+  pub interpolation: Interpolation,
+  /// Tension, continuity and bias, used by `Interpolation::KochanekBartels`.
+  #[serde(default)]
+  pub tcb: Tcb
 }
 
 impl<T> Key<T> {
@@ -36,13 +41,51 @@ impl<T> Key<T> {
     Key {
       t: t,
       value: value,
-      interpolation: interpolation
+      interpolation: interpolation,
+      tcb: Tcb::default()
+    }
+  }
+
+  /// Set the tension, continuity and bias of the key.
+  pub fn with_tcb(self, tcb: Tcb) -> Self {
+    Key {
+      tcb: tcb,
+      ..self
+    }
+  }
+}
+
+/// Tension, continuity and bias parameters of a Kochanek–Bartels key.
+///
+/// All parameters are zero by default, which makes a Kochanek–Bartels spline reduce to a
+/// Catmull-Rom spline.
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Tcb {
+  /// How sharply the curve bends at the key (`1` is tight, `-1` is round).
+  #[serde(default)]
+  pub tension: f32,
+  /// How abruptly the tangent changes at the key (non-zero values create corners).
+  #[serde(default)]
+  pub continuity: f32,
+  /// Direction of the curve as it passes through the key (`-1` favors the next key, `1` favors the
+  /// previous one).
+  #[serde(default)]
+  pub bias: f32
+}
+
+impl Tcb {
+  /// Create new tension, continuity and bias parameters.
+  pub fn new(tension: f32, continuity: f32, bias: f32) -> Self {
+    Tcb {
+      tension: tension,
+      continuity: continuity,
+      bias: bias
     }
   }
 }
 
 /// Interpolation mode.
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum Interpolation {
   /// Hold a `Key` until the time passes the normalized step threshold, in which case the next
   /// key is used.
@@ -60,7 +103,18 @@ pub enum Interpolation {
   Cosine,
   /// Catmull-Rom interpolation.
   #[serde(rename = "catmull_rom")]
-  CatmullRom
+  CatmullRom,
+  /// Kochanek–Bartels interpolation, driven by the tension, continuity and bias of the keys.
+  ///
+  /// Like `CatmullRom`, it requires *four* keys.
+  #[serde(rename = "kochanek_bartels")]
+  KochanekBartels,
+  /// Eased interpolation between a key and the next one.
+  ///
+  /// The normalized time between the two keys is remapped by the easing function before being
+  /// passed to `Interpolate::lerp`.
+  #[serde(rename = "ease")]
+  Ease(EasingKind, EasingDirection)
 }
 
 impl Default for Interpolation {
@@ -139,6 +193,25 @@ impl<T> Spline<T> {
 
           Some(Interpolate::cubic_hermite((cpm0.value, cpm0.t), (cp0.value, cp0.t), (cp1.value, cp1.t), (cpm1.value, cpm1.t), nt))
         }
+      },
+      Interpolation::KochanekBartels => {
+        // same as Catmull Rom, we need at least four points
+        if i == 0 || i >= keys.len() - 2 {
+          None
+        } else {
+          let cp1 = &keys[i+1];
+          let cpm0 = &keys[i-1];
+          let cpm1 = &keys[i+2];
+          let nt = normalize_time(t, cp0, cp1);
+
+          Some(Interpolate::kochanek_bartels((cpm0.value, cpm0.t), (cp0.value, cp0.t, cp0.tcb), (cp1.value, cp1.t, cp1.tcb), (cpm1.value, cpm1.t), nt))
+        }
+      },
+      Interpolation::Ease(kind, dir) => {
+        let cp1 = &keys[i+1];
+        let nt = normalize_time(t, cp0, cp1);
+
+        Some(Interpolate::lerp(cp0.value, cp1.value, kind.ease(dir, nt)))
       }
     }
   }
@@ -223,11 +296,21 @@ impl<T> Load for Spline<T> where T: 'static + SplineDeserializerAdapter {
     let keys: Vec<Key<T::Deserialized>> = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
 
     Ok(Spline::from_keys(keys.into_iter().map(|key|
-      Key::new(key.t, T::from_deserialized(key.value), key.interpolation)
+      Key::new(key.t, T::from_deserialized(key.value), key.interpolation).with_tcb(key.tcb)
     ).collect()).into())
   }
 }
 
+/// Splines are saved as the list of their keys, which is the format they’re loaded from.
+impl<T> Save for Spline<T> where T: 'static + Serialize + SplineDeserializerAdapter {
+  fn save(&self, key: &Self::Key) -> Result<(), SaveError> {
+    let path = key.key_to_path();
+
+    let file = File::create(&path).map_err(|_| SaveError::CannotWrite(path))?;
+    to_writer_pretty(file, &self.keys).map_err(|e| SaveError::SerializationFailed(format!("{:?}", e)))
+  }
+}
+
 /// Spline deserializer adapter used to deserialize splines which keys’ values types don’t directly
 /// implement deserialization.
 pub trait SplineDeserializerAdapter {
@@ -327,6 +410,12 @@ pub trait Interpolate: Copy {
   fn cubic_hermite(_: (Self, Time), a: (Self, Time), b: (Self, Time), _: (Self, Time), t: Time) -> Self {
     Self::lerp(a.0, b.0, t)
   }
+  /// Kochanek–Bartels interpolation.
+  ///
+  /// Default to `Self::cubic_hermite`.
+  fn kochanek_bartels(x: (Self, Time), a: (Self, Time, Tcb), b: (Self, Time, Tcb), y: (Self, Time), t: Time) -> Self {
+    Self::cubic_hermite(x, (a.0, a.1), (b.0, b.1), y, t)
+  }
 }
 
 impl Interpolate for f32 {
@@ -337,6 +426,10 @@ impl Interpolate for f32 {
   fn cubic_hermite(x: (Self, Time), a: (Self, Time), b: (Self, Time), y: (Self, Time), t: Time) -> Self {
     cubic_hermite(x, a, b, y, t)
   }
+
+  fn kochanek_bartels(x: (Self, Time), a: (Self, Time, Tcb), b: (Self, Time, Tcb), y: (Self, Time), t: Time) -> Self {
+    kochanek_bartels(x, a, b, y, t)
+  }
 }
 
 impl Interpolate for V2<f32> {
@@ -347,6 +440,10 @@ impl Interpolate for V2<f32> {
   fn cubic_hermite(x: (Self, Time), a: (Self, Time), b: (Self, Time), y: (Self, Time), t: Time) -> Self {
     cubic_hermite(x, a, b, y, t)
   }
+
+  fn kochanek_bartels(x: (Self, Time), a: (Self, Time, Tcb), b: (Self, Time, Tcb), y: (Self, Time), t: Time) -> Self {
+    kochanek_bartels(x, a, b, y, t)
+  }
 }
 
 impl Interpolate for V3<f32> {
@@ -357,6 +454,10 @@ impl Interpolate for V3<f32> {
   fn cubic_hermite(x: (Self, Time), a: (Self, Time), b: (Self, Time), y: (Self, Time), t: Time) -> Self {
     cubic_hermite(x, a, b, y, t)
   }
+
+  fn kochanek_bartels(x: (Self, Time), a: (Self, Time, Tcb), b: (Self, Time, Tcb), y: (Self, Time), t: Time) -> Self {
+    kochanek_bartels(x, a, b, y, t)
+  }
 }
 
 impl Interpolate for V4<f32> {
@@ -367,6 +468,10 @@ impl Interpolate for V4<f32> {
   fn cubic_hermite(x: (Self, Time), a: (Self, Time), b: (Self, Time), y: (Self, Time), t: Time) -> Self {
     cubic_hermite(x, a, b, y, t)
   }
+
+  fn kochanek_bartels(x: (Self, Time), a: (Self, Time, Tcb), b: (Self, Time, Tcb), y: (Self, Time), t: Time) -> Self {
+    kochanek_bartels(x, a, b, y, t)
+  }
 }
 
 impl Interpolate for Quat<f32> {
@@ -401,6 +506,34 @@ pub fn cubic_hermite<T>(x: (T, Time), a: (T, Time), b: (T, Time), y: (T, Time),
   a.0 * (two_t3 - three_t2 + 1.) + m0 * (t3 - 2. * t2 + t) + b.0 * (-two_t3 + three_t2) + m1 * (t3 - t2)
 }
 
+// Default implementation of Interpolate::kochanek_bartels.
+//
+// The tangents are computed the same way as in cubic_hermite, but each side of a key is weighted
+// by its tension, continuity and bias. With all parameters set to zero, this is exactly
+// cubic_hermite.
+pub fn kochanek_bartels<T>(x: (T, Time), a: (T, Time, Tcb), b: (T, Time, Tcb), y: (T, Time), t: Time) -> T
+    where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Time, Output = T> + Div<Time, Output = T> {
+  // time stuff
+  let t2 = t * t;
+  let t3 = t2 * t;
+  let two_t3 = 2. * t3;
+  let three_t2 = 3. * t2;
+
+  // outgoing tangent of a
+  let Tcb { tension, continuity, bias } = a.2;
+  let in_w = (1. - tension) * (1. + bias) * (1. + continuity);
+  let out_w = (1. - tension) * (1. - bias) * (1. - continuity);
+  let m0 = ((a.0 - x.0) * in_w + (b.0 - a.0) * out_w) / (b.1 - x.1);
+
+  // incoming tangent of b
+  let Tcb { tension, continuity, bias } = b.2;
+  let in_w = (1. - tension) * (1. + bias) * (1. - continuity);
+  let out_w = (1. - tension) * (1. - bias) * (1. + continuity);
+  let m1 = ((b.0 - a.0) * in_w + (y.0 - b.0) * out_w) / (y.1 - a.1);
+
+  a.0 * (two_t3 - three_t2 + 1.) + m0 * (t3 - 2. * t2 + t) + b.0 * (-two_t3 + three_t2) + m1 * (t3 - t2)
+}
+
 // Normalize a time ([0;1]) given two control points.
 pub fn normalize_time<T>(t: Time, cp: &Key<T>, cp1: &Key<T>) -> Time {
   (t - cp.t) / (cp1.t - cp.t)
@@ -0,0 +1,296 @@
+//! Musical time.
+//!
+//! Demos are cut to music. This module provides a `TempoMap` – a list of tempo changes, each one
+//! giving a BPM and a time signature from a given bar on – that converts back and forth between
+//! seconds and *musical time* (bars and beats).
+//!
+//! Bars and beats are zero-based: the very first beat of the production is at bar `0`, beat `0`.
+//! A beat is the unit the BPM is expressed in.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+
+use anim::edit::Time;
+
+/// A position expressed in bars and beats.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MusicalTime {
+  /// Bar.
+  pub bar: u32,
+  /// Beat in the bar; can be fractional.
+  #[serde(default)]
+  pub beat: f64
+}
+
+impl MusicalTime {
+  pub fn new(bar: u32, beat: f64) -> Self {
+    MusicalTime {
+      bar: bar,
+      beat: beat
+    }
+  }
+}
+
+/// A time that can be expressed either in seconds or in bars and beats.
+///
+/// In JSON, seconds are written as a plain number and musical time as an object, like
+/// `{ "bar": 16, "beat": 2 }`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ManifestTime {
+  Seconds(Time),
+  Musical(MusicalTime)
+}
+
+impl Default for ManifestTime {
+  fn default() -> Self {
+    ManifestTime::Seconds(0.)
+  }
+}
+
+impl From<Time> for ManifestTime {
+  fn from(t: Time) -> Self {
+    ManifestTime::Seconds(t)
+  }
+}
+
+impl From<MusicalTime> for ManifestTime {
+  fn from(t: MusicalTime) -> Self {
+    ManifestTime::Musical(t)
+  }
+}
+
+/// A tempo change, effective from a given bar on.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TempoChange {
+  /// Bar at which the change occurs.
+  pub bar: u32,
+  /// Beats per minute.
+  pub bpm: f64,
+  /// Number of beats in a bar; must not be zero.
+  #[serde(default = "default_beats_per_bar")]
+  pub beats_per_bar: u32
+}
+
+fn default_beats_per_bar() -> u32 {
+  4
+}
+
+impl TempoChange {
+  pub fn new(bar: u32, bpm: f64, beats_per_bar: u32) -> Self {
+    TempoChange {
+      bar: bar,
+      bpm: bpm,
+      beats_per_bar: beats_per_bar
+    }
+  }
+
+  /// Does the change have a positive BPM and at least one beat per bar?
+  ///
+  /// Conversions are meaningless with a tempo map holding invalid changes.
+  pub fn is_valid(&self) -> bool {
+    self.bpm > 0. && self.beats_per_bar > 0
+  }
+
+  fn beat_dur(&self) -> Time {
+    60. / self.bpm
+  }
+
+  fn bar_dur(&self) -> Time {
+    self.beat_dur() * self.beats_per_bar as Time
+  }
+}
+
+/// Information about the beat being played at a given time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatInfo {
+  /// Current bar.
+  pub bar: u32,
+  /// Current beat in the bar.
+  pub beat: u32,
+  /// Progression in the current beat, in `[0;1[`.
+  pub phase: f64,
+  /// Current tempo.
+  pub bpm: f64
+}
+
+/// A tempo map.
+///
+/// The changes don’t need to be sorted; they’re sorted once when the map is created, along with the
+/// time at which each one starts, so that conversions don’t recompute them. An empty map is 120 BPM
+/// in 4/4. If the first change doesn’t occur at bar `0`, its tempo is used from the beginning.
+///
+/// In JSON, a tempo map is written as the list of its changes.
+#[derive(Clone, Debug)]
+pub struct TempoMap {
+  changes: Vec<TempoChange>,
+  // sorted changes along with the time at which they start; never empty, and the first change
+  // always starts at bar 0
+  segments: Vec<(TempoChange, Time)>
+}
+
+impl TempoMap {
+  /// Create a tempo map out of tempo changes.
+  pub fn new(changes: Vec<TempoChange>) -> Self {
+    let segments = segments(&changes);
+
+    TempoMap {
+      changes: changes,
+      segments: segments
+    }
+  }
+
+  /// Create a tempo map with a single, constant tempo.
+  pub fn constant(bpm: f64, beats_per_bar: u32) -> Self {
+    Self::new(vec![TempoChange::new(0, bpm, beats_per_bar)])
+  }
+
+  /// Tempo changes, as given when creating the map.
+  pub fn changes(&self) -> &[TempoChange] {
+    &self.changes
+  }
+
+  /// Is the map empty?
+  pub fn is_empty(&self) -> bool {
+    self.changes.is_empty()
+  }
+
+  /// Convert musical time to seconds.
+  ///
+  /// Beats don’t need to fit in the bar: the ones overflowing into the next tempo changes are played
+  /// at their tempos.
+  pub fn to_seconds(&self, m: MusicalTime) -> Time {
+    let i = self.last_segment(|s| s.0.bar <= m.bar);
+    let (mut change, start) = self.segments[i];
+    let mut t = start + (m.bar - change.bar) as Time * change.bar_dur();
+    let mut beats = m.beat;
+
+    for &(next, next_start) in &self.segments[i + 1..] {
+      // beats left before the next change
+      let left = (next_start - t) / change.beat_dur();
+
+      if !(beats > left) {
+        break;
+      }
+
+      t = next_start;
+      beats -= left;
+      change = next;
+    }
+
+    t + beats * change.beat_dur()
+  }
+
+  /// Convert seconds to musical time. Negative times are clamped to `0`.
+  pub fn to_musical(&self, t: Time) -> MusicalTime {
+    let t = t.max(0.);
+    let (change, start) = self.segments[self.last_segment(|s| s.1 <= t)];
+
+    let beats = (t - start) / change.beat_dur();
+    let bars = (beats / change.beats_per_bar as f64).floor();
+
+    MusicalTime::new(change.bar + bars as u32, beats - bars * change.beats_per_bar as f64)
+  }
+
+  /// Resolve a manifest time into seconds.
+  pub fn seconds(&self, t: ManifestTime) -> Time {
+    match t {
+      ManifestTime::Seconds(t) => t,
+      ManifestTime::Musical(m) => self.to_seconds(m)
+    }
+  }
+
+  /// Resolve a manifest duration starting at a given time into seconds.
+  ///
+  /// A duration in bars and beats is counted from the start, so that each tempo change it spans is
+  /// honoured.
+  pub fn duration(&self, start: Time, dur: ManifestTime) -> Time {
+    match dur {
+      ManifestTime::Seconds(dur) => dur,
+      ManifestTime::Musical(dur) => {
+        let start = self.to_musical(start);
+        self.to_seconds(MusicalTime::new(start.bar + dur.bar, start.beat + dur.beat)) - self.to_seconds(start)
+      }
+    }
+  }
+
+  /// Express a time in seconds the way another manifest time is written: in bars and beats if it
+  /// is, in seconds otherwise.
+  pub fn to_manifest_time(&self, t: Time, like: ManifestTime) -> ManifestTime {
+    match like {
+      ManifestTime::Seconds(_) => ManifestTime::Seconds(t),
+      ManifestTime::Musical(_) => ManifestTime::Musical(self.to_musical(t))
+    }
+  }
+
+  /// Get information about the beat being played at a given time.
+  pub fn beat_info(&self, t: Time) -> BeatInfo {
+    let m = self.to_musical(t);
+    let beat = m.beat.floor();
+
+    BeatInfo {
+      bar: m.bar,
+      beat: beat as u32,
+      phase: m.beat - beat,
+      bpm: self.segments[self.last_segment(|s| s.0.bar <= m.bar)].0.bpm
+    }
+  }
+
+  // Index of the last segment satisfying a predicate that holds for a prefix of the segments, or of
+  // the first one.
+  fn last_segment<P>(&self, pred: P) -> usize where P: Fn(&(TempoChange, Time)) -> bool {
+    // the ordering never says equal, so that the search ends right after the prefix
+    let i = self.segments.binary_search_by(|s| if pred(s) { Ordering::Less } else { Ordering::Greater }).unwrap_err();
+    i.max(1) - 1
+  }
+}
+
+impl Default for TempoMap {
+  fn default() -> Self {
+    Self::new(Vec::new())
+  }
+}
+
+impl PartialEq for TempoMap {
+  fn eq(&self, rhs: &Self) -> bool {
+    self.changes == rhs.changes
+  }
+}
+
+impl Serialize for TempoMap {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+    self.changes.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for TempoMap {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+    Vec::deserialize(deserializer).map(TempoMap::new)
+  }
+}
+
+// Sorted changes along with the time at which they start. The first change always starts at bar 0.
+fn segments(changes: &[TempoChange]) -> Vec<(TempoChange, Time)> {
+  let mut changes = changes.to_vec();
+  changes.sort_by_key(|c| c.bar);
+
+  if changes.is_empty() {
+    changes.push(TempoChange::new(0, 120., 4));
+  }
+
+  changes[0].bar = 0;
+
+  let mut segments = Vec::with_capacity(changes.len());
+  let mut start = 0.;
+
+  for (i, change) in changes.iter().enumerate() {
+    if i > 0 {
+      let prev = &changes[i - 1];
+      start += (change.bar - prev.bar) as Time * prev.bar_dur();
+    }
+
+    segments.push((*change, start));
+  }
+
+  segments
+}
@@ -0,0 +1,238 @@
+//! Timeline validation.
+//!
+//! Mistakes in a `TimelineManifest` often only show up as black frames at runtime. This module
+//! provides a validation pass that looks for such mistakes and reports them as a list of
+//! `TimelineIssue`s, each one giving the time range and the clips involved.
+
+use std::f64;
+use std::fmt;
+
+use anim::edit::{cmp_time, Time, TimelineManifest};
+
+/// A time range, as `(start, end)`.
+pub type TimeRange = (Time, Time);
+
+/// An issue found in a timeline.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimelineIssue {
+  /// A cut has an input time greater than its output time.
+  InvertedCut {
+    /// Track of the cut.
+    track: usize,
+    /// Clip of the cut.
+    clip: String,
+    /// Input time, in seconds.
+    in_time: Time,
+    /// Output time, in seconds.
+    out_time: Time
+  },
+  /// A cut has a time mapping with a zero speed or a negative duration.
+  InvalidTimeMapping {
+    /// Track of the cut.
+    track: usize,
+    /// Clip of the cut.
+    clip: String,
+    /// What’s wrong with the time mapping.
+    reason: String
+  },
+  /// Two cuts of the same track overlap.
+  OverlappingCuts {
+    /// Track of the cuts.
+    track: usize,
+    /// Clips of the two cuts, in the order they start.
+    clips: (String, String),
+    /// Time range on which both cuts are active.
+    range: TimeRange
+  },
+  /// No cut is active on this time range.
+  Gap {
+    /// Time range left uncovered.
+    range: TimeRange
+  },
+  /// Several cuts are active on this time range but neither an overlap nor the blend modes of
+  /// their tracks can resolve them.
+  MissingOverlap {
+    /// Clips of the active cuts.
+    clips: Vec<String>,
+    /// Time range on which the cuts are active together.
+    range: TimeRange
+  },
+  /// A tempo change doesn’t have a positive BPM or has no beat per bar.
+  InvalidTempo {
+    /// Bar at which the change occurs.
+    bar: u32,
+    /// Beats per minute of the change.
+    bpm: f64,
+    /// Number of beats in a bar of the change.
+    beats_per_bar: u32
+  }
+}
+
+impl fmt::Display for TimelineIssue {
+  fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    match *self {
+      TimelineIssue::InvertedCut { track, ref clip, in_time, out_time } => {
+        write!(f, "track {}: cut of {:?} has an input time ({}) greater than its output time ({})", track, clip, in_time, out_time)
+      },
+      TimelineIssue::InvalidTimeMapping { track, ref clip, ref reason } => {
+        write!(f, "track {}: cut of {:?} has an invalid time mapping: {}", track, clip, reason)
+      },
+      TimelineIssue::OverlappingCuts { track, ref clips, range } => {
+        write!(f, "track {}: cuts of {:?} and {:?} overlap on [{}; {}]", track, clips.0, clips.1, range.0, range.1)
+      },
+      TimelineIssue::Gap { range } => {
+        write!(f, "nothing is played on ]{}; {}[", range.0, range.1)
+      },
+      TimelineIssue::MissingOverlap { ref clips, range } => {
+        write!(f, "{:?} are played at the same time on ]{}; {}[ but no overlap is defined", clips, range.0, range.1)
+      },
+      TimelineIssue::InvalidTempo { bar, bpm, beats_per_bar } => {
+        write!(f, "bar {}: the tempo ({} BPM, {} beats per bar) must have a positive BPM and at least one beat per bar", bar, bpm, beats_per_bar)
+      }
+    }
+  }
+}
+
+impl TimelineManifest {
+  /// Validate the manifest and return all the issues found, sorted by time.
+  ///
+  /// Gaps are looked for from `0` to the end of the last cut. Cuts meeting at an instant – one
+  /// ending where the next one starts – don’t overlap, as the first one gives way to the next one
+  /// when played; see `Timeline::play`.
+  pub fn validate(&self) -> Vec<TimelineIssue> {
+    let mut issues: Vec<_> = self.tempo.changes().iter().filter(|change| !change.is_valid()).map(|change| {
+      TimelineIssue::InvalidTempo { bar: change.bar, bpm: change.bpm, beats_per_bar: change.beats_per_bar }
+    }).collect();
+    // (start, end, clip, track) of all valid cuts
+    let mut cuts = Vec::new();
+
+    for (track_id, track) in self.tracks.iter().enumerate() {
+      let mut track_cuts = Vec::new();
+
+      for cut in &track.cuts {
+        let (in_time, out_time) = cut.slice(&self.tempo);
+
+        if in_time > out_time {
+          issues.push(TimelineIssue::InvertedCut {
+            track: track_id,
+            clip: cut.clip.clone(),
+            in_time: in_time,
+            out_time: out_time
+          });
+        } else if let Err(reason) = cut.time_mapping.check() {
+          issues.push(TimelineIssue::InvalidTimeMapping {
+            track: track_id,
+            clip: cut.clip.clone(),
+            reason: reason
+          });
+        } else {
+          let inst_time = self.tempo.seconds(cut.inst_time);
+          track_cuts.push((inst_time, inst_time + cut.dur(&self.tempo), cut.clip.as_str(), track_id));
+        }
+      }
+
+      track_cuts.sort_by(|a, b| cmp_time(a.0, b.0));
+
+      for (i, a) in track_cuts.iter().enumerate() {
+        for b in track_cuts[i + 1..].iter().take_while(|b| b.0 < a.1) {
+          issues.push(TimelineIssue::OverlappingCuts {
+            track: track_id,
+            clips: (a.2.to_owned(), b.2.to_owned()),
+            range: (b.0, a.1.min(b.1))
+          });
+        }
+      }
+
+      cuts.extend(track_cuts);
+    }
+
+    // sweep the elementary segments between all boundaries
+    let mut bounds: Vec<Time> = vec![0.];
+    bounds.extend(cuts.iter().flat_map(|&(a, b, _, _)| vec![a, b]));
+    let overlaps: Vec<_> = self.overlaps.iter().map(|o| {
+      let inst_time = self.tempo.seconds(o.inst_time);
+      (inst_time, inst_time + self.tempo.duration(inst_time, o.dur))
+    }).collect();
+
+    bounds.extend(overlaps.iter().flat_map(|&(a, b)| vec![a, b]));
+    bounds.sort_by(|a, b| cmp_time(*a, *b));
+    bounds.dedup();
+
+    let end = cuts.iter().fold(0., |end: Time, c| end.max(c.1));
+    let mut segment_issues: Vec<TimelineIssue> = Vec::new();
+
+    for w in bounds.windows(2) {
+      let (a, b) = (w[0], w[1]);
+
+      if a >= end {
+        break;
+      }
+
+      let active_cuts: Vec<_> = cuts.iter().filter(|c| c.0 <= a && b <= c.1).collect();
+      let active: Vec<String> = active_cuts.iter().map(|c| c.2.to_owned()).collect();
+      let covered = overlaps.iter().any(|o| o.0 <= a && b <= o.1) || blends(self, active_cuts.iter().map(|c| c.3));
+
+      let issue = if active.is_empty() {
+        TimelineIssue::Gap { range: (a, b) }
+      } else if active.len() > 1 && !covered {
+        TimelineIssue::MissingOverlap { clips: active, range: (a, b) }
+      } else {
+        continue;
+      };
+
+      // extend the previous issue if it’s the same as the one on this segment
+      let merged = segment_issues.last_mut().map_or(false, |last| merge(last, &issue));
+
+      if !merged {
+        segment_issues.push(issue);
+      }
+    }
+
+    issues.extend(segment_issues);
+    issues.sort_by(|a, b| cmp_time(issue_start(a), issue_start(b)));
+    issues
+  }
+}
+
+// Can the given active tracks be resolved by blending? Several cuts of the same track collide.
+fn blends<I>(manifest: &TimelineManifest, tracks: I) -> bool where I: Iterator<Item = usize> {
+  let mut tracks: Vec<_> = tracks.collect();
+  tracks.sort_by_key(|&track| (manifest.tracks[track].priority, track));
+
+  tracks.windows(2).all(|w| w[0] != w[1]) && tracks.iter().skip(1).all(|&track| manifest.tracks[track].blend.is_some())
+}
+
+// Merge an issue into the previous one if they’re of the same kind and contiguous.
+fn merge(last: &mut TimelineIssue, next: &TimelineIssue) -> bool {
+  match (last, next) {
+    (&mut TimelineIssue::Gap { ref mut range }, &TimelineIssue::Gap { range: next_range }) => {
+      if range.1 == next_range.0 {
+        range.1 = next_range.1;
+        true
+      } else {
+        false
+      }
+    },
+    (&mut TimelineIssue::MissingOverlap { ref clips, ref mut range },
+     &TimelineIssue::MissingOverlap { clips: ref next_clips, range: next_range }) => {
+      if range.1 == next_range.0 && clips == next_clips {
+        range.1 = next_range.1;
+        true
+      } else {
+        false
+      }
+    },
+    _ => false
+  }
+}
+
+fn issue_start(issue: &TimelineIssue) -> Time {
+  match *issue {
+    TimelineIssue::InvertedCut { .. } |
+    TimelineIssue::InvalidTimeMapping { .. } |
+    TimelineIssue::InvalidTempo { .. } => f64::NEG_INFINITY,
+    TimelineIssue::OverlappingCuts { range, .. } => range.0,
+    TimelineIssue::Gap { range } => range.0,
+    TimelineIssue::MissingOverlap { range, .. } => range.0
+  }
+}
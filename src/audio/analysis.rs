@@ -0,0 +1,240 @@
+//! Audio analysis.
+//!
+//! Analysis works on a soundtrack decoded in memory as `Pcm`, so that its results only depend on
+//! the time they’re computed at – and not on what the playback is doing – which makes
+//! audio-reactive effects reproducible and testable offline.
+//!
+//! An `Analyzer` gives, at any time, the spectrum of the last frames played along with their RMS and
+//! peak levels.
+
+use std::f32::consts::PI;
+use std::fs::File;
+use std::path::Path;
+
+use audio::{AudioError, open_decoder};
+use audio::stream::Decoder;
+
+/// Decoded mono samples, in *[-1; 1]*.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pcm {
+  rate: u32,
+  samples: Vec<f32>
+}
+
+impl Pcm {
+  pub fn new(rate: u32, samples: Vec<f32>) -> Self {
+    Pcm {
+      rate: rate,
+      samples: samples
+    }
+  }
+
+  /// Decode a whole stream, downmixing its channels.
+  pub fn decode<D>(mut decoder: D) -> Self where D: Decoder {
+    let channels = decoder.channels().max(1) as usize;
+    let mut samples = Vec::with_capacity(decoder.frames().unwrap_or(0) as usize);
+
+    while let Some(packet) = decoder.packet() {
+      for frame in packet.chunks(channels) {
+        let sum = frame.iter().fold(0., |sum, &x| sum + x as f32);
+        samples.push(sum / (channels as f32 * 32768.));
+      }
+    }
+
+    Pcm::new(decoder.rate(), samples)
+  }
+
+  /// Decode a whole soundtrack.
+  pub fn open<P>(path: P) -> Result<Self, AudioError> where P: AsRef<Path> {
+    let file = File::open(path.as_ref()).map_err(|_| AudioError::FileNotFound(path.as_ref().to_owned()))?;
+    open_decoder(file).map(Pcm::decode)
+  }
+
+  pub fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  pub fn samples(&self) -> &[f32] {
+    &self.samples
+  }
+
+  /// Duration in seconds.
+  pub fn dur(&self) -> f32 {
+    self.samples.len() as f32 / self.rate.max(1) as f32
+  }
+
+  /// Sample at a given frame; zero outside of the stream.
+  pub fn sample(&self, frame: i64) -> f32 {
+    if frame < 0 {
+      0.
+    } else {
+      self.samples.get(frame as usize).cloned().unwrap_or(0.)
+    }
+  }
+}
+
+/// A frequency band, in Hz. The lower bound is included, the upper one isn’t.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Band {
+  pub low: f32,
+  pub high: f32
+}
+
+impl Band {
+  pub fn new(low: f32, high: f32) -> Self {
+    Band {
+      low: low,
+      high: high
+    }
+  }
+}
+
+/// Analysis of a window of samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spectrum {
+  /// Magnitude of every frequency bin, from 0 Hz to the Nyquist frequency excluded.
+  ///
+  /// A sine of amplitude *a* centered on a bin yields a magnitude of *a* in that bin.
+  pub magnitudes: Vec<f32>,
+  /// Energy – sum of the squared magnitudes – in every band of the analyzer.
+  pub bands: Vec<f32>,
+  /// Root mean square level.
+  pub rms: f32,
+  /// Peak level.
+  pub peak: f32
+}
+
+/// Spectrum analyzer.
+pub struct Analyzer {
+  pcm: Pcm,
+  bands: Vec<Band>,
+  // Hann window
+  hann: Vec<f32>,
+  // factor turning FFT outputs into amplitudes
+  norm: f32
+}
+
+impl Analyzer {
+  /// Analyze windows of `window` frames, which must be a power of two.
+  ///
+  /// The default bands are the bass (below 250 Hz), the mids (up to 4 kHz) and the trebles.
+  pub fn new(pcm: Pcm, window: usize) -> Self {
+    assert!(window.is_power_of_two() && window >= 2, "the analysis window must be a power of two");
+
+    let hann: Vec<_> = (0..window).map(|i| 0.5 - 0.5 * (2. * PI * i as f32 / window as f32).cos()).collect();
+    let norm = 2. / hann.iter().fold(0., |sum, &w| sum + w);
+
+    Analyzer {
+      pcm: pcm,
+      bands: vec![Band::new(0., 250.), Band::new(250., 4000.), Band::new(4000., ::std::f32::INFINITY)],
+      hann: hann,
+      norm: norm
+    }
+  }
+
+  pub fn pcm(&self) -> &Pcm {
+    &self.pcm
+  }
+
+  /// Number of frames analyzed at once.
+  pub fn window(&self) -> usize {
+    self.hann.len()
+  }
+
+  pub fn bands(&self) -> &[Band] {
+    &self.bands
+  }
+
+  pub fn set_bands(&mut self, bands: Vec<Band>) {
+    self.bands = bands;
+  }
+
+  /// Frequency of a bin of the spectrum, in Hz.
+  pub fn bin_freq(&self, bin: usize) -> f32 {
+    bin as f32 * self.pcm.rate as f32 / self.window() as f32
+  }
+
+  /// Analyze the window of frames ending at `t`, in seconds.
+  pub fn analyze(&self, t: f32) -> Spectrum {
+    self.analyze_frame((t as f64 * self.pcm.rate as f64).round() as i64)
+  }
+
+  /// Analyze the window of frames ending right before a given frame.
+  pub fn analyze_frame(&self, end: i64) -> Spectrum {
+    let window = self.window();
+    let samples: Vec<_> = (end - window as i64..end).map(|frame| self.pcm.sample(frame)).collect();
+
+    let rms = (samples.iter().fold(0., |sum, &x| sum + x * x) / window as f32).sqrt();
+    let peak = samples.iter().fold(0., |peak: f32, &x| peak.max(x.abs()));
+
+    let mut re: Vec<_> = samples.iter().zip(&self.hann).map(|(&x, &w)| x * w).collect();
+    let mut im = vec![0.; window];
+    fft(&mut re, &mut im);
+
+    let magnitudes: Vec<_> = (0..window / 2).map(|i| (re[i] * re[i] + im[i] * im[i]).sqrt() * self.norm).collect();
+
+    let bands = self.bands.iter().map(|band| {
+      magnitudes.iter().enumerate()
+        .filter(|&(i, _)| { let freq = self.bin_freq(i); freq >= band.low && freq < band.high })
+        .fold(0., |energy, (_, &m)| energy + m * m)
+    }).collect();
+
+    Spectrum {
+      magnitudes: magnitudes,
+      bands: bands,
+      rms: rms,
+      peak: peak
+    }
+  }
+}
+
+// In-place radix-2 FFT; the length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+  let n = re.len();
+
+  // bit-reversal permutation
+  let mut j = 0;
+
+  for i in 1..n {
+    let mut bit = n >> 1;
+
+    while j & bit != 0 {
+      j ^= bit;
+      bit >>= 1;
+    }
+
+    j |= bit;
+
+    if i < j {
+      re.swap(i, j);
+      im.swap(i, j);
+    }
+  }
+
+  // butterflies
+  let mut len = 2;
+
+  while len <= n {
+    let half = len / 2;
+    let angle = -2. * PI / len as f32;
+    let mut start = 0;
+
+    while start < n {
+      for k in 0..half {
+        let (sin, cos) = (angle * k as f32).sin_cos();
+        let (a, b) = (start + k, start + k + half);
+        let tr = re[b] * cos - im[b] * sin;
+        let ti = re[b] * sin + im[b] * cos;
+
+        re[b] = re[a] - tr;
+        im[b] = im[a] - ti;
+        re[a] += tr;
+        im[a] += ti;
+      }
+
+      start += len;
+    }
+
+    len <<= 1;
+  }
+}
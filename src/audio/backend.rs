@@ -0,0 +1,435 @@
+//! Audio backends.
+//!
+//! A backend plays buffers of PCM data queued one after the other. Besides the OpenAL backend,
+//! this module provides:
+//!
+//! - `NullBackend`, which plays nothing but advances a clock – either a virtual one you drive
+//!   yourself or the wall clock
+//! - `OfflineBackend`, which renders what it plays into memory, as PCM or WAV data
+//!
+//! Both work without any audio device, so that playback and synchronization can be tested
+//! headlessly.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use audio::AudioError;
+
+/// A sink playing queued buffers of interleaved 16-bit samples.
+///
+/// Buffers that were entirely played stay in the queue until they’re unqueued.
+pub trait Backend {
+  /// Maximum number of buffers that can be queued at once.
+  fn capacity(&self) -> usize;
+
+  /// Format – channels and rate – a stream in a given format is played in. Streams are converted
+  /// to that format before being queued.
+  ///
+  /// Any format is accepted by default.
+  fn format(&self, channels: u16, rate: u32) -> (u16, u32) {
+    (channels, rate)
+  }
+
+  /// Queue a buffer at the back of the queue.
+  ///
+  /// On failure, nothing is queued.
+  fn queue(&mut self, samples: &[i16], channels: u16, rate: u32) -> Result<(), AudioError>;
+
+  /// Remove the buffers that were entirely played and return how many were removed.
+  fn unqueue_processed(&mut self) -> usize;
+
+  /// Stop playing and drop all the queued buffers.
+  fn stop(&mut self);
+
+  /// Playback offset, in frames, from the start of the oldest queued buffer.
+  fn offset(&mut self) -> u64;
+
+  fn play(&mut self);
+
+  fn pause(&mut self);
+
+  /// Is the backend playing? A backend stops by itself when it runs out of buffers.
+  fn is_playing(&mut self) -> bool;
+}
+
+impl<B> Backend for Box<B> where B: ?Sized + Backend {
+  fn capacity(&self) -> usize {
+    (**self).capacity()
+  }
+
+  fn format(&self, channels: u16, rate: u32) -> (u16, u32) {
+    (**self).format(channels, rate)
+  }
+
+  fn queue(&mut self, samples: &[i16], channels: u16, rate: u32) -> Result<(), AudioError> {
+    (**self).queue(samples, channels, rate)
+  }
+
+  fn unqueue_processed(&mut self) -> usize {
+    (**self).unqueue_processed()
+  }
+
+  fn stop(&mut self) {
+    (**self).stop()
+  }
+
+  fn offset(&mut self) -> u64 {
+    (**self).offset()
+  }
+
+  fn play(&mut self) {
+    (**self).play()
+  }
+
+  fn pause(&mut self) {
+    (**self).pause()
+  }
+
+  fn is_playing(&mut self) -> bool {
+    (**self).is_playing()
+  }
+}
+
+// A queued buffer, as seen by the null backend.
+#[derive(Clone, Copy, Debug)]
+struct NullBuffer {
+  frames: u64,
+  rate: u32
+}
+
+impl NullBuffer {
+  fn dur(&self) -> f64 {
+    self.frames as f64 / self.rate.max(1) as f64
+  }
+}
+
+/// A backend playing nothing but advancing a clock.
+pub struct NullBackend {
+  queue: VecDeque<NullBuffer>,
+  // seconds played since the start of the oldest queued buffer
+  elapsed: f64,
+  playing: bool,
+  // last time the wall clock was read, for real time backends
+  last_tick: Option<Instant>
+}
+
+impl NullBackend {
+  /// A backend driven by a virtual clock; call `advance` to make time pass.
+  pub fn new() -> Self {
+    NullBackend {
+      queue: VecDeque::new(),
+      elapsed: 0.,
+      playing: false,
+      last_tick: None
+    }
+  }
+
+  /// A backend driven by the wall clock.
+  pub fn realtime() -> Self {
+    NullBackend {
+      last_tick: Some(Instant::now()),
+      ..Self::new()
+    }
+  }
+
+  /// Make `dt` seconds pass.
+  pub fn advance(&mut self, dt: f64) {
+    if !self.playing {
+      return;
+    }
+
+    let total = self.queue.iter().fold(0., |total, buffer| total + buffer.dur());
+    self.elapsed += dt;
+
+    if self.elapsed >= total {
+      // out of buffers
+      self.elapsed = total;
+      self.playing = false;
+    }
+  }
+
+  fn tick(&mut self) {
+    let now = Instant::now();
+
+    if let Some(last) = self.last_tick {
+      let dt = now.duration_since(last);
+      self.advance(dt.as_secs() as f64 + dt.subsec_nanos() as f64 * 1e-9);
+      self.last_tick = Some(now);
+    }
+  }
+}
+
+impl Default for NullBackend {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Backend for NullBackend {
+  fn capacity(&self) -> usize {
+    4
+  }
+
+  fn queue(&mut self, samples: &[i16], channels: u16, rate: u32) -> Result<(), AudioError> {
+    if self.queue.len() >= self.capacity() {
+      return Err(full());
+    }
+
+    self.tick();
+    self.queue.push_back(NullBuffer {
+      frames: (samples.len() / channels.max(1) as usize) as u64,
+      rate: rate
+    });
+
+    Ok(())
+  }
+
+  fn unqueue_processed(&mut self) -> usize {
+    self.tick();
+
+    let mut unqueued = 0;
+
+    while let Some(buffer) = self.queue.front().cloned() {
+      // tolerate rounding errors, so that the last buffer is unqueued once played
+      if self.elapsed + 1e-9 < buffer.dur() {
+        break;
+      }
+
+      self.elapsed = (self.elapsed - buffer.dur()).max(0.);
+      self.queue.pop_front();
+      unqueued += 1;
+    }
+
+    unqueued
+  }
+
+  fn stop(&mut self) {
+    self.queue.clear();
+    self.elapsed = 0.;
+    self.playing = false;
+  }
+
+  fn offset(&mut self) -> u64 {
+    self.tick();
+    self.queue.front().map_or(0, |buffer| (self.elapsed * buffer.rate as f64) as u64)
+  }
+
+  fn play(&mut self) {
+    self.tick();
+    self.playing = !self.queue.is_empty();
+  }
+
+  fn pause(&mut self) {
+    self.tick();
+    self.playing = false;
+  }
+
+  fn is_playing(&mut self) -> bool {
+    self.tick();
+    self.playing
+  }
+}
+
+/// A backend rendering what it plays into memory.
+///
+/// Time passes when calling `render`, which plays a given number of frames; silence is rendered
+/// while paused or out of buffers. The format of the output is either fixed with `with_format` or
+/// the one of the first queued buffer.
+pub struct OfflineBackend {
+  queue: VecDeque<Vec<i16>>,
+  // frames played since the start of the oldest queued buffer
+  played: u64,
+  playing: bool,
+  channels: u16,
+  rate: u32,
+  output: Vec<i16>
+}
+
+impl OfflineBackend {
+  pub fn new() -> Self {
+    OfflineBackend {
+      queue: VecDeque::new(),
+      played: 0,
+      playing: false,
+      channels: 0,
+      rate: 0,
+      output: Vec::new()
+    }
+  }
+
+  /// A backend rendering with a given number of channels and rate; streams are converted to it.
+  pub fn with_format(channels: u16, rate: u32) -> Self {
+    OfflineBackend {
+      channels: channels,
+      rate: rate,
+      ..Self::new()
+    }
+  }
+
+  /// Play the given number of frames, appending them to the output.
+  pub fn render(&mut self, frames: u64) {
+    let channels = self.channels.max(1) as usize;
+
+    for _ in 0..frames {
+      let next = if self.playing { self.frame_at(self.played) } else { None };
+
+      match next {
+        Some((buffer, frame)) => {
+          let samples = &self.queue[buffer][frame * channels..(frame + 1) * channels];
+          self.output.extend_from_slice(samples);
+          self.played += 1;
+        },
+        None => {
+          self.playing = false;
+          self.output.extend(::std::iter::repeat(0).take(channels));
+        }
+      }
+    }
+  }
+
+  /// Rendered interleaved samples.
+  pub fn output(&self) -> &[i16] {
+    &self.output
+  }
+
+  /// Number of channels of the output.
+  pub fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  /// Sample rate of the output.
+  pub fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  /// Rendered data as a 16-bit PCM WAV file.
+  pub fn to_wav(&self) -> Vec<u8> {
+    let data_len = (self.output.len() * 2) as u32;
+    let block_align = self.channels * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    push_u32(&mut wav, 36 + data_len);
+    wav.extend_from_slice(b"WAVEfmt ");
+    push_u32(&mut wav, 16);
+    push_u16(&mut wav, 1); // PCM
+    push_u16(&mut wav, self.channels);
+    push_u32(&mut wav, self.rate);
+    push_u32(&mut wav, self.rate * block_align as u32);
+    push_u16(&mut wav, block_align);
+    push_u16(&mut wav, 16);
+    wav.extend_from_slice(b"data");
+    push_u32(&mut wav, data_len);
+
+    for &sample in &self.output {
+      push_u16(&mut wav, sample as u16);
+    }
+
+    wav
+  }
+
+  // (buffer, frame in buffer) of a frame counted from the start of the oldest queued buffer
+  fn frame_at(&self, mut frame: u64) -> Option<(usize, usize)> {
+    let channels = self.channels.max(1) as u64;
+
+    for (i, buffer) in self.queue.iter().enumerate() {
+      let frames = buffer.len() as u64 / channels;
+
+      if frame < frames {
+        return Some((i, frame as usize));
+      }
+
+      frame -= frames;
+    }
+
+    None
+  }
+}
+
+impl Default for OfflineBackend {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Backend for OfflineBackend {
+  fn capacity(&self) -> usize {
+    4
+  }
+
+  fn format(&self, channels: u16, rate: u32) -> (u16, u32) {
+    if self.channels == 0 {
+      (channels, rate)
+    } else {
+      (self.channels, self.rate)
+    }
+  }
+
+  fn queue(&mut self, samples: &[i16], channels: u16, rate: u32) -> Result<(), AudioError> {
+    if self.queue.len() >= self.capacity() {
+      return Err(full());
+    }
+
+    if self.channels == 0 {
+      self.channels = channels;
+      self.rate = rate;
+    }
+
+    self.queue.push_back(samples.to_vec());
+
+    Ok(())
+  }
+
+  fn unqueue_processed(&mut self) -> usize {
+    let channels = self.channels.max(1) as u64;
+    let mut unqueued = 0;
+
+    while let Some(frames) = self.queue.front().map(|buffer| buffer.len() as u64 / channels) {
+      if self.played < frames {
+        break;
+      }
+
+      self.played -= frames;
+      self.queue.pop_front();
+      unqueued += 1;
+    }
+
+    unqueued
+  }
+
+  fn stop(&mut self) {
+    self.queue.clear();
+    self.played = 0;
+    self.playing = false;
+  }
+
+  fn offset(&mut self) -> u64 {
+    self.played
+  }
+
+  fn play(&mut self) {
+    self.playing = !self.queue.is_empty();
+  }
+
+  fn pause(&mut self) {
+    self.playing = false;
+  }
+
+  fn is_playing(&mut self) -> bool {
+    self.playing
+  }
+}
+
+fn full() -> AudioError {
+  AudioError::QueueFailed("all the buffers are queued".to_owned())
+}
+
+fn push_u16(v: &mut Vec<u8>, x: u16) {
+  v.push(x as u8);
+  v.push((x >> 8) as u8);
+}
+
+fn push_u32(v: &mut Vec<u8>, x: u32) {
+  push_u16(v, x as u16);
+  push_u16(v, (x >> 16) as u16);
+}
@@ -0,0 +1,276 @@
+//! Offline onset, tempo and beat detection.
+//!
+//! Detection runs over the whole soundtrack, out of the spectra of an `Analyzer`:
+//!
+//! - the *onset envelope* is the spectral flux – how much the spectrum grows from one analysis
+//!   frame to the next; `onset_envelope` gives it as a `Spline<f32>`, normalized in *[0; 1]*
+//! - onsets are the peaks of the envelope standing out of their neighbourhood
+//! - the tempo is the period that best correlates the envelope with itself, and beats are placed
+//!   along that period where they hit the most onset strength
+//!
+//! The result is a set of `Beats` markers, which can be saved and loaded through the store, or turned
+//! into an event track of a timeline manifest.
+
+use serde_json::{Value, from_reader, to_writer_pretty};
+use std::fs::File;
+use std::path::PathBuf;
+
+use anim::edit::{EventManifest, EventTrackManifest};
+use anim::spline::{Interpolation, Key, Spline, Time};
+use anim::tempo::ManifestTime;
+use audio::analysis::Analyzer;
+use sys::resource::{CacheKey, Load, LoadError, LoadResult, Save, SaveError, Store, StoreKey};
+
+// compression factor of the magnitudes before computing the flux
+const LOG_COMPRESSION: f32 = 100.;
+// number of frames on each side a peak must dominate to be an onset
+const PEAK_RADIUS: usize = 3;
+// number of frames on each side the mean a peak must stand out of is computed on
+const MEAN_RADIUS: usize = 8;
+
+/// Parameters of the detection.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct BeatConfig {
+  /// Number of frames between two analysis frames.
+  pub hop: usize,
+  /// How much, relative to the strongest one, an onset must stand out of its neighbourhood.
+  pub threshold: f32,
+  /// Slowest tempo considered, in beats per minute.
+  pub min_bpm: f32,
+  /// Fastest tempo considered, in beats per minute.
+  pub max_bpm: f32
+}
+
+impl Default for BeatConfig {
+  fn default() -> Self {
+    BeatConfig {
+      hop: 512,
+      threshold: 0.1,
+      min_bpm: 60.,
+      max_bpm: 200.
+    }
+  }
+}
+
+/// Detected onsets and beats.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Beats {
+  /// Tempo, in beats per minute; `0` if none could be estimated.
+  pub bpm: f32,
+  /// Times of the onsets, in seconds.
+  pub onsets: Vec<Time>,
+  /// Times of the beats, in seconds.
+  pub beats: Vec<Time>
+}
+
+impl Beats {
+  /// Detect the onsets and beats of the soundtrack of an analyzer.
+  pub fn detect(analyzer: &Analyzer, config: &BeatConfig) -> Self {
+    let flux = flux(analyzer, config);
+    let max = flux.iter().fold(0., |max: f32, &x| max.max(x));
+
+    if max <= 0. {
+      return Beats {
+        bpm: 0.,
+        onsets: Vec::new(),
+        beats: Vec::new()
+      };
+    }
+
+    let onsets = (0..flux.len())
+      .filter(|&n| is_onset(&flux, n, config.threshold * max))
+      .map(|n| frame_time(analyzer, config, n as f32))
+      .collect();
+
+    let frame_rate = analyzer.pcm().rate() as f32 / config.hop.max(1) as f32;
+    let (bpm, beats) = match period(&flux, frame_rate, config) {
+      Some(period) => {
+        let phase = phase(&flux, period);
+        let beats = (0..).map(|k| phase + k as f32 * period)
+          .take_while(|&frame| frame < flux.len() as f32)
+          .map(|frame| frame_time(analyzer, config, frame))
+          .collect();
+
+        (60. * frame_rate / period, beats)
+      },
+      None => (0., Vec::new())
+    };
+
+    Beats {
+      bpm: bpm,
+      onsets: onsets,
+      beats: beats
+    }
+  }
+
+  /// Event track with a `"beat"` event on every beat and an `"onset"` event on every onset, ready
+  /// to be added to a `TimelineManifest`.
+  pub fn event_track(&self) -> EventTrackManifest {
+    let mut events: Vec<_> = self.beats.iter().map(|&t| (t, "beat"))
+      .chain(self.onsets.iter().map(|&t| (t, "onset")))
+      .collect();
+    events.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    EventTrackManifest {
+      events: events.into_iter().map(|(t, name)| EventManifest {
+        time: ManifestTime::Seconds(t as f64),
+        name: name.to_owned(),
+        payload: Value::Null
+      }).collect()
+    }
+  }
+}
+
+/// Onset envelope of the soundtrack of an analyzer, normalized in *[0; 1]*.
+pub fn onset_envelope(analyzer: &Analyzer, config: &BeatConfig) -> Spline<f32> {
+  let flux = flux(analyzer, config);
+  let max = flux.iter().fold(0., |max: f32, &x| max.max(x));
+  let scale = if max > 0. { 1. / max } else { 0. };
+
+  Spline::from_keys(flux.iter().enumerate().map(|(n, &x)| {
+    Key::new(frame_time(analyzer, config, n as f32), x * scale, Interpolation::Linear)
+  }).collect())
+}
+
+// Time of an analysis frame: the center of its window.
+fn frame_time(analyzer: &Analyzer, config: &BeatConfig, frame: f32) -> Time {
+  (frame * config.hop.max(1) as f32 + analyzer.window() as f32 / 2.) / analyzer.pcm().rate() as f32
+}
+
+// Spectral flux of every analysis frame.
+fn flux(analyzer: &Analyzer, config: &BeatConfig) -> Vec<f32> {
+  let hop = config.hop.max(1);
+  let window = analyzer.window();
+  let len = analyzer.pcm().samples().len();
+
+  if len < window {
+    return Vec::new();
+  }
+
+  let frames = (len - window) / hop + 1;
+  let mut flux = Vec::with_capacity(frames);
+  let mut prev: Option<Vec<f32>> = None;
+
+  for n in 0..frames {
+    let spectrum = analyzer.analyze_frame((n * hop + window) as i64);
+    let mags: Vec<_> = spectrum.magnitudes.iter().map(|&m| (1. + LOG_COMPRESSION * m).ln()).collect();
+
+    flux.push(match prev {
+      Some(ref prev) => mags.iter().zip(prev).fold(0., |sum, (&m, &p)| sum + (m - p).max(0.)),
+      None => 0.
+    });
+
+    prev = Some(mags);
+  }
+
+  flux
+}
+
+// Is a frame a peak of the flux, standing out of the local mean by at least delta?
+fn is_onset(flux: &[f32], n: usize, delta: f32) -> bool {
+  let x = flux[n];
+  let peak_start = n.saturating_sub(PEAK_RADIUS);
+  let peak_end = (n + PEAK_RADIUS + 1).min(flux.len());
+
+  // strictly above what precedes, so that plateaus give a single onset
+  if flux[peak_start..n].iter().any(|&y| y >= x) || flux[n + 1..peak_end].iter().any(|&y| y > x) {
+    return false;
+  }
+
+  let mean_start = n.saturating_sub(MEAN_RADIUS);
+  let mean_end = (n + MEAN_RADIUS + 1).min(flux.len());
+  let mean = flux[mean_start..mean_end].iter().fold(0., |sum, &y| sum + y) / (mean_end - mean_start) as f32;
+
+  x > mean + delta
+}
+
+// Beat period, in frames, maximizing the autocorrelation of the flux.
+//
+// The autocorrelation isn’t normalized by the number of overlapping frames, so that it decreases
+// with the lag; otherwise, multiples of the period would score as high as the period itself.
+fn period(flux: &[f32], frame_rate: f32, config: &BeatConfig) -> Option<f32> {
+  let n = flux.len();
+  let min_lag = ((60. * frame_rate / config.max_bpm).floor() as usize).max(1);
+  let max_lag = ((60. * frame_rate / config.min_bpm).ceil() as usize).min(n.saturating_sub(1));
+
+  if min_lag > max_lag {
+    return None;
+  }
+
+  let autocorrelation = |lag: usize| {
+    flux[..n - lag].iter().zip(&flux[lag..]).fold(0., |sum, (&a, &b)| sum + a * b)
+  };
+
+  let scores: Vec<_> = (min_lag..max_lag + 1).map(&autocorrelation).collect();
+  let best = (0..scores.len()).fold(0, |best, i| if scores[i] > scores[best] { i } else { best });
+
+  if scores[best] <= 0. {
+    return None;
+  }
+
+  // refine the period between the neighbouring lags with a parabola
+  let offset = if best > 0 && best + 1 < scores.len() {
+    let (a, b, c) = (scores[best - 1], scores[best], scores[best + 1]);
+    let d = a - 2. * b + c;
+
+    if d < 0. { 0.5 * (a - c) / d } else { 0. }
+  } else {
+    0.
+  };
+
+  Some((min_lag + best) as f32 + offset)
+}
+
+// First beat, in frames, so that beats hit the most flux.
+fn phase(flux: &[f32], period: f32) -> f32 {
+  let score = |phase: f32| {
+    (0..).map(|k| (phase + k as f32 * period).round() as usize)
+      .take_while(|&frame| frame < flux.len())
+      .fold(0., |sum, frame| sum + flux[frame])
+  };
+
+  let phases = period.ceil() as usize;
+  let best = (0..phases).fold((0, -1.), |(best, best_score), phase| {
+    let s = score(phase as f32);
+
+    if s > best_score { (phase, s) } else { (best, best_score) }
+  });
+
+  best.0 as f32
+}
+
+/// Key of `Beats` in the store.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct BeatsKey(pub String);
+
+impl CacheKey for BeatsKey {
+  type Target = Beats;
+}
+
+impl StoreKey for BeatsKey {
+  fn key_to_path(&self) -> PathBuf {
+    self.0.clone().into()
+  }
+}
+
+impl Load for Beats {
+  type Key = BeatsKey;
+
+  fn load(key: &Self::Key, _: &mut Store) -> Result<LoadResult<Self>, LoadError> {
+    let path = key.key_to_path();
+
+    let file = File::open(&path).map_err(|_| LoadError::FileNotFound(path))?;
+    let res: Self = from_reader(file).map_err(|e| LoadError::ParseFailed(format!("{:?}", e)))?;
+
+    Ok(res.into())
+  }
+}
+
+impl Save for Beats {
+  fn save(&self, key: &Self::Key) -> Result<(), SaveError> {
+    let path = key.key_to_path();
+
+    let file = File::create(&path).map_err(|_| SaveError::CannotWrite(path))?;
+    to_writer_pretty(file, self).map_err(|e| SaveError::SerializationFailed(format!("{:?}", e)))
+  }
+}
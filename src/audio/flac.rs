@@ -0,0 +1,347 @@
+//! FLAC decoding.
+
+use claxon;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::mem;
+
+use audio::AudioError;
+use audio::stream::Decoder;
+
+// below that many bytes, the frame holding a seeked sample is looked for by decoding linearly
+const BISECT_BYTES: u64 = 16 * 1024;
+// number of bytes read at once while looking for a frame header
+const PROBE_BYTES: usize = 4096;
+// metadata block types
+const STREAMINFO: u8 = 0;
+const SEEKTABLE: u8 = 3;
+// sample number of the placeholder points of a seek table
+const PLACEHOLDER: u64 = 0xFFFF_FFFF_FFFF_FFFF;
+
+/// Streaming FLAC decoder.
+///
+/// Seeking starts from the closest frame known to precede the seeked sample – found in the seek
+/// table of the stream or by a previous seek – and bisects the stream on frame headers while the
+/// remaining range is large. The frames found that way are remembered, so that seeking again to the
+/// same place – like the start of a loop region – decodes at most a few frames.
+pub struct FlacDecoder<R> where R: Read + Seek {
+  // None only if restarting the stream failed
+  reader: Option<claxon::FlacReader<Spliced<R>>>,
+  // stream header and streaminfo block, replayed in front of the frames after a seek
+  header: Vec<u8>,
+  // length of the stream, in bytes
+  len: u64,
+  // (first sample, offset) of known frames, sorted by sample
+  seek_points: Vec<(u64, u64)>,
+  // number of samples of the frames of fixed-blocksize streams
+  block_size: Option<u64>,
+  rate: u32,
+  channels: u16,
+  bits: u32,
+  frames: Option<u64>,
+  // frames to drop from the next decoded blocks, after a seek
+  skip: u64,
+  // buffer recycled from block to block
+  buffer: Vec<i32>
+}
+
+impl<R> FlacDecoder<R> where R: Read + Seek {
+  /// Start decoding a stream.
+  pub fn new(mut reader: R) -> Result<Self, AudioError> {
+    let magic = read_bytes(&mut reader, 4).map_err(io_failed)?;
+
+    if &magic[..] != b"fLaC" {
+      return Err(AudioError::UnknownFormat);
+    }
+
+    let mut header = magic;
+    let mut seek_points = Vec::new();
+
+    // only the streaminfo block is kept; the seek table is read on the way
+    loop {
+      let block_header = read_bytes(&mut reader, 4).map_err(io_failed)?;
+      let last = block_header[0] & 0x80 != 0;
+      let len = (block_header[1] as u64) << 16 | (block_header[2] as u64) << 8 | block_header[3] as u64;
+
+      match block_header[0] & 0x7F {
+        STREAMINFO => {
+          header.push(0x80 | STREAMINFO);
+          header.extend_from_slice(&block_header[1..]);
+          header.extend(read_bytes(&mut reader, len as usize).map_err(io_failed)?);
+        },
+        SEEKTABLE => {
+          let table = read_bytes(&mut reader, len as usize).map_err(io_failed)?;
+
+          for point in table.chunks(18).filter(|point| point.len() == 18) {
+            let (sample, offset) = (u64_at(point, 0), u64_at(point, 8));
+
+            if sample != PLACEHOLDER {
+              seek_points.push((sample, offset));
+            }
+          }
+        },
+        _ => {
+          reader.seek(SeekFrom::Current(len as i64)).map_err(io_failed)?;
+        }
+      }
+
+      if last {
+        break;
+      }
+    }
+
+    let frames_start = reader.seek(SeekFrom::Current(0)).map_err(io_failed)?;
+    let len = reader.seek(SeekFrom::End(0)).map_err(io_failed)?;
+    reader.seek(SeekFrom::Start(frames_start)).map_err(io_failed)?;
+
+    let reader = claxon::FlacReader::new(Spliced::new(&header, reader)).map_err(|e| AudioError::DecodingFailed(format!("{:?}", e)))?;
+    let info = reader.streaminfo();
+
+    // seek table offsets are relative to the first frame
+    for point in &mut seek_points {
+      point.1 += frames_start;
+    }
+
+    seek_points.push((0, frames_start));
+    seek_points.sort();
+    seek_points.dedup_by_key(|point| point.0);
+
+    Ok(FlacDecoder {
+      header: header,
+      len: len,
+      seek_points: seek_points,
+      block_size: if info.min_block_size == info.max_block_size { Some(info.max_block_size as u64) } else { None },
+      rate: info.sample_rate,
+      channels: info.channels as u16,
+      bits: info.bits_per_sample,
+      frames: info.samples,
+      reader: Some(reader),
+      skip: 0,
+      buffer: Vec::new()
+    })
+  }
+
+  // scale a sample to 16 bits
+  fn sample(&self, sample: i32) -> i16 {
+    if self.bits >= 16 {
+      (sample >> (self.bits - 16)) as i16
+    } else {
+      (sample << (16 - self.bits)) as i16
+    }
+  }
+
+  // first sample of a block
+  //
+  // Fixed-blocksize frames store their number, which claxon multiplies by their own size; that’s
+  // wrong for the last frame, which is shorter.
+  fn block_start(&self, block: &claxon::Block) -> u64 {
+    match self.block_size {
+      Some(size) if block.duration() > 0 => block.time() / block.duration() as u64 * size,
+      _ => block.time()
+    }
+  }
+
+  // remember the first sample of the frame at a given offset
+  fn remember(&mut self, sample: u64, offset: u64) {
+    if let Err(i) = self.seek_points.binary_search_by_key(&sample, |point| point.0) {
+      self.seek_points.insert(i, (sample, offset));
+    }
+  }
+
+  // (first sample, offset) of the first frame starting in [from; to[
+  fn probe(&self, reader: &mut R, from: u64, to: u64) -> Option<(u64, u64)> {
+    let mut pos = from;
+
+    while pos < to {
+      if reader.seek(SeekFrom::Start(pos)).is_err() {
+        return None;
+      }
+
+      // keep a byte more, so that a sync code across two windows is found
+      let len = PROBE_BYTES.min((to - pos) as usize + 1);
+      let mut window = Vec::with_capacity(len);
+
+      match reader.by_ref().take(len as u64).read_to_end(&mut window) {
+        Ok(read) if read >= 2 => (),
+        _ => return None
+      }
+
+      for i in 0..window.len() - 1 {
+        // frame headers start with a 14-bit sync code, followed by a reserved zero bit
+        if window[i] != 0xFF || window[i + 1] & 0xFE != 0xF8 || pos + i as u64 >= to {
+          continue;
+        }
+
+        let offset = pos + i as u64;
+
+        if let Some(sample) = self.first_sample_at(reader, offset) {
+          return Some((sample, offset));
+        }
+      }
+
+      pos += window.len() as u64 - 1;
+    }
+
+    None
+  }
+
+  // first sample of the frame at a given offset, if a valid frame is there
+  fn first_sample_at(&self, reader: &mut R, offset: u64) -> Option<u64> {
+    if reader.seek(SeekFrom::Start(offset)).is_err() {
+      return None;
+    }
+
+    let mut flac = match claxon::FlacReader::new(Spliced::new(&self.header, reader)) {
+      Ok(flac) => flac,
+      Err(_) => return None
+    };
+
+    // the CRC of the frame rejects sync codes found in the middle of another frame
+    match flac.blocks().read_next_or_eof(Vec::new()) {
+      Ok(Some(block)) => Some(self.block_start(&block)),
+      _ => None
+    }
+  }
+}
+
+impl<R> Decoder for FlacDecoder<R> where R: Read + Seek {
+  fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn frames(&self) -> Option<u64> {
+    self.frames
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    loop {
+      let buffer = mem::replace(&mut self.buffer, Vec::new());
+
+      let block = match self.reader.as_mut().map(|reader| reader.blocks().read_next_or_eof(buffer)) {
+        Some(Ok(Some(block))) => block,
+        Some(Ok(None)) | None => return None,
+        Some(Err(e)) => {
+          err!("cannot decode FLAC block: {:?}", e);
+          return None;
+        }
+      };
+
+      // drop the frames before the seeked one
+      let skip = self.skip.min(block.duration() as u64);
+      self.skip -= skip;
+
+      let mut samples = Vec::with_capacity((block.duration() as usize - skip as usize) * block.channels() as usize);
+
+      for i in skip as u32..block.duration() {
+        for c in 0..block.channels() {
+          samples.push(self.sample(block.sample(c, i)));
+        }
+      }
+
+      self.buffer = block.into_buffer();
+
+      if !samples.is_empty() {
+        return Some(samples);
+      }
+    }
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    if self.frames.map_or(false, |frames| frame > frames) {
+      return false;
+    }
+
+    let mut reader = match self.reader.take() {
+      Some(reader) => reader.into_inner().into_inner(),
+      None => return false
+    };
+
+    // closest known frames around the seeked one
+    let i = match self.seek_points.binary_search_by_key(&frame, |point| point.0) {
+      Ok(i) => i,
+      Err(i) => i.max(1) - 1
+    };
+    let (mut start, mut offset) = self.seek_points[i];
+    let mut end = self.seek_points.get(i + 1).map_or(self.len, |point| point.1);
+
+    while end > offset + BISECT_BYTES {
+      let mid = offset + (end - offset) / 2;
+
+      match self.probe(&mut reader, mid, end) {
+        Some((sample, at)) => {
+          self.remember(sample, at);
+
+          if sample <= frame {
+            start = sample;
+            offset = at;
+          } else {
+            // the seeked frame starts before the first frame following the middle
+            end = mid;
+          }
+        },
+        None => end = mid
+      }
+    }
+
+    if reader.seek(SeekFrom::Start(offset)).is_err() {
+      return false;
+    }
+
+    match claxon::FlacReader::new(Spliced::new(&self.header, reader)) {
+      Ok(reader) => {
+        self.reader = Some(reader);
+        self.skip = frame - start;
+        true
+      },
+      Err(e) => {
+        err!("cannot restart FLAC stream: {:?}", e);
+        false
+      }
+    }
+  }
+}
+
+// A reader reading a header before the rest of a stream, so that claxon can start decoding at any
+// frame.
+struct Spliced<R> {
+  header: Cursor<Vec<u8>>,
+  reader: R
+}
+
+impl<R> Spliced<R> {
+  fn new(header: &[u8], reader: R) -> Self {
+    Spliced {
+      header: Cursor::new(header.to_vec()),
+      reader: reader
+    }
+  }
+
+  fn into_inner(self) -> R {
+    self.reader
+  }
+}
+
+impl<R> Read for Spliced<R> where R: Read {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self.header.read(buf)? {
+      0 => self.reader.read(buf),
+      read => Ok(read)
+    }
+  }
+}
+
+fn io_failed(e: io::Error) -> AudioError {
+  AudioError::DecodingFailed(e.to_string())
+}
+
+fn read_bytes<R>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> where R: Read {
+  let mut bytes = vec![0; len];
+  reader.read_exact(&mut bytes).map(|_| bytes)
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+  bytes[offset..offset + 8].iter().fold(0, |x, &byte| x << 8 | byte as u64)
+}
@@ -0,0 +1,505 @@
+//! Soundtrack playback.
+//!
+//! The soundtrack is streamed: it’s decoded a chunk at a time into a small ring of buffers queued on
+//! a `Backend`, which is refilled as playback goes on. The decoding side lives in the `stream`
+//! module.
+//!
+//! Ogg Vorbis, WAV and FLAC soundtracks are supported; the format is picked out of the content of
+//! the file with `open_decoder`. Soundtracks are played at their own rate and with their own
+//! channels, unless the backend requires another format, in which case they’re converted by a
+//! `Resampler`, which also makes it possible to change the playback speed.
+//!
+//! Besides playing and pausing, the cursor can be moved in seconds, a region can be played over
+//! and over, and what happens at the end of the track is chosen with an `EndPolicy`.
+//!
+//! The `analysis` module provides spectrum analysis of the soundtrack, for audio-reactive effects,
+//! and the `beat` module detects its onsets and beats offline, for timeline authoring.
+//!
+//! Playback goes through OpenAL with `Audio::open`. Any other `Backend` can be used with
+//! `Audio::new` – for instance the null and offline backends of the `backend` module, which don’t
+//! require an audio device. Failures are reported as `AudioError`s; if the demo must run anyway,
+//! `Audio::open_or_silent` falls back to silent playback, which still has a working clock.
+
+pub mod analysis;
+pub mod backend;
+pub mod beat;
+pub mod flac;
+pub mod ogg;
+pub mod openal;
+pub mod resample;
+pub mod stream;
+pub mod wav;
+
+use alto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use audio::analysis::{Analyzer, Spectrum};
+use audio::backend::{Backend, NullBackend};
+use audio::flac::FlacDecoder;
+use audio::ogg::VorbisDecoder;
+use audio::openal::OpenAlBackend;
+use audio::resample::Resampler;
+use audio::stream::{BufferRing, Decoder, Silence, Streamer};
+use audio::wav::WavDecoder;
+
+// number of streamed buffers per second of soundtrack
+const CHUNKS_PER_SEC: u32 = 4;
+// number of buffers in the ring
+const RING_LEN: usize = 4;
+// format of the silence played when the soundtrack cannot be opened
+const SILENCE_CHANNELS: u16 = 2;
+const SILENCE_RATE: u32 = 44100;
+
+/// Error that might occur while opening or playing a soundtrack.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AudioError {
+  /// The soundtrack file was not found.
+  FileNotFound(PathBuf),
+  /// The format of the soundtrack is not supported.
+  UnknownFormat,
+  /// The soundtrack couldn’t be decoded.
+  DecodingFailed(String),
+  /// OpenAL couldn’t be loaded, or no device or context could be opened.
+  OpenAlFailed(String),
+  /// A buffer couldn’t be queued to the backend.
+  QueueFailed(String)
+}
+
+/// What happens when the end of the soundtrack is reached.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EndPolicy {
+  /// Stop playing and rewind to the start.
+  Stop,
+  /// Start over, without any gap.
+  Loop,
+  /// Stop playing and stay at the end.
+  Hold
+}
+
+impl Default for EndPolicy {
+  /// `EndPolicy::Loop` is the default.
+  fn default() -> Self {
+    EndPolicy::Loop
+  }
+}
+
+/// The audio object you can use to interact with the soundtrack.
+///
+/// Times are in seconds of the soundtrack, whatever the playback speed.
+pub struct Audio<B> {
+  /// Length of the track.
+  len: f32,
+  /// Rate the soundtrack is played at.
+  rate: u32,
+  /// Number of channels the soundtrack is played with.
+  channels: u16,
+  /// Backend the soundtrack is played with.
+  backend: B,
+  /// Decoder of the soundtrack, converted to the format of the backend and to the playback speed.
+  streamer: Streamer<Resampler<Box<Decoder>>>,
+  /// Buffers queued on the backend.
+  ring: BufferRing,
+  /// Whether the soundtrack should be playing; the backend might be stopped because of an underrun.
+  playing: bool,
+  /// Region played over and over, if any.
+  loop_region: Option<(f32, f32)>,
+  /// What to do at the end of the track.
+  end_policy: EndPolicy
+}
+
+impl<B> Audio<B> where B: Backend {
+  /// Play a soundtrack with a given backend.
+  pub fn new<P>(track_path: P, backend: B) -> Result<Self, AudioError> where P: AsRef<Path> {
+    let decoder = open_track(track_path)?;
+    Ok(Self::from_decoder(decoder, backend))
+  }
+
+  /// Play a decoded stream with a given backend.
+  ///
+  /// The stream is converted if the backend cannot play its format.
+  pub fn from_decoder(decoder: Box<Decoder>, backend: B) -> Self {
+    let (src_channels, src_rate) = (decoder.channels(), decoder.rate());
+    let (channels, rate) = backend.format(src_channels, src_rate);
+
+    if (channels, rate) != (src_channels, src_rate) {
+      info!("converting soundtrack from {} channel(s) at {} Hz to {} channel(s) at {} Hz", src_channels, src_rate, channels, rate);
+    }
+
+    let decoder = Resampler::new(decoder, rate, channels);
+
+    // compute the length of soundtrack
+    let len = match decoder.frames() {
+      Some(frames) => frames as f32 / rate as f32,
+      None => {
+        warn!("cannot compute the length of the soundtrack");
+        0.
+      }
+    };
+
+    let mut audio = Audio {
+      len: len,
+      rate: rate,
+      channels: channels,
+      backend: backend,
+      streamer: Streamer::new(decoder, (rate / CHUNKS_PER_SEC) as usize),
+      ring: BufferRing::new(),
+      playing: false,
+      loop_region: None,
+      end_policy: EndPolicy::default()
+    };
+
+    audio.update();
+    audio
+  }
+
+  pub fn len(&self) -> f32 {
+    self.len
+  }
+
+  /// Rate the soundtrack is played at.
+  pub fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  /// Number of channels the soundtrack is played with.
+  pub fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  pub fn backend(&self) -> &B {
+    &self.backend
+  }
+
+  pub fn backend_mut(&mut self) -> &mut B {
+    &mut self.backend
+  }
+
+  pub fn cursor(&mut self) -> f32 {
+    self.update();
+
+    let offset = self.backend.offset();
+
+    // nothing queued means the end of the track was reached
+    let frame = self.ring.cursor(offset).unwrap_or(self.streamer.pos());
+
+    self.frame_to_secs(frame)
+  }
+
+  /// Analyze the soundtrack at the cursor.
+  pub fn analyze(&mut self, analyzer: &Analyzer) -> Spectrum {
+    analyzer.analyze(self.cursor())
+  }
+
+  /// Move the cursor to a normalized position in the track.
+  pub fn set_cursor(&mut self, t: f32) {
+    let len = self.len;
+    self.seek(t * len);
+  }
+
+  /// Move the cursor to a given time. Times out of the track are clamped.
+  pub fn seek(&mut self, t: f32) {
+    let t = if self.len > 0. { t.max(0.).min(self.len) } else { t.max(0.) };
+    let frame = self.secs_to_frame(t);
+
+    self.seek_frame(frame);
+  }
+
+  /// Region played over and over, as start and end times.
+  pub fn loop_region(&self) -> Option<(f32, f32)> {
+    self.loop_region
+  }
+
+  /// Play a region over and over, or the whole track with `None`.
+  ///
+  /// Once the end of the region is reached, playback goes on from its start without any gap. Empty
+  /// regions are ignored.
+  pub fn set_loop_region(&mut self, region: Option<(f32, f32)>) {
+    match region {
+      Some((start, end)) if end <= start => {
+        warn!("ignoring empty loop region [{}; {}[", start, end);
+      },
+      _ => {
+        // drop what was queued according to the previous region
+        let t = self.cursor();
+        self.loop_region = region;
+        self.seek(t);
+      }
+    }
+  }
+
+  /// Playback speed; `1` is the normal speed.
+  pub fn speed(&self) -> f32 {
+    self.streamer.decoder().speed()
+  }
+
+  /// Set the playback speed: at `0.5`, the track plays at half speed and an octave lower. Speeds
+  /// that are not positive are ignored.
+  pub fn set_speed(&mut self, speed: f32) {
+    if speed <= 0. {
+      warn!("ignoring playback speed {}", speed);
+      return;
+    }
+
+    // positions in the stream depend on the speed, so the cursor must be read before the change
+    let t = self.cursor();
+    self.streamer.decoder_mut().set_speed(speed);
+    self.seek(t);
+  }
+
+  pub fn end_policy(&self) -> EndPolicy {
+    self.end_policy
+  }
+
+  pub fn set_end_policy(&mut self, policy: EndPolicy) {
+    self.end_policy = policy;
+  }
+
+  /// Is the soundtrack playing?
+  pub fn is_playing(&self) -> bool {
+    self.playing
+  }
+
+  pub fn play(&mut self) {
+    // restart a track held at its end
+    if self.ring.is_empty() {
+      self.seek_frame(0);
+    }
+
+    self.playing = true;
+    self.update();
+    self.backend.play();
+  }
+
+  pub fn pause(&mut self) {
+    self.playing = false;
+    self.backend.pause();
+  }
+
+  pub fn toggle(&mut self) -> bool {
+    if self.playing {
+      self.pause();
+      false
+    } else {
+      self.play();
+      true
+    }
+  }
+
+  /// Unqueue the buffers that were played and queue freshly decoded ones.
+  ///
+  /// This is done automatically when reading the cursor.
+  pub fn update(&mut self) {
+    for _ in 0..self.backend.unqueue_processed() {
+      self.ring.pop();
+    }
+
+    let region = self.loop_region.map(|(start, end)| (self.secs_to_frame(start), self.secs_to_frame(end)));
+    let restart = match (region, self.end_policy) {
+      (Some((start, _)), _) => Some(start),
+      (None, EndPolicy::Loop) => Some(0),
+      _ => None
+    };
+    // whether the stream was rewound with nothing streamed since, so that empty streams don’t loop
+    // forever
+    let mut rewound = false;
+    // whether the backend refused a buffer, in which case it’ll be streamed again on the next update
+    let mut refused = false;
+
+    while self.ring.len() < self.backend.capacity() {
+      let mut chunk = match self.streamer.next_chunk() {
+        Some(chunk) => chunk,
+        None => {
+          match restart {
+            Some(frame) if !rewound => {
+              rewound = true;
+              self.seek_stream(frame);
+              continue;
+            },
+            _ => break
+          }
+        }
+      };
+
+      let mut wrap = false;
+
+      // cut the chunk at the end of the loop region
+      if let Some((start, end)) = region {
+        if chunk.start >= end {
+          if rewound {
+            break;
+          }
+
+          rewound = true;
+          self.seek_stream(start);
+          continue;
+        }
+
+        if chunk.start + chunk.frames(self.channels) > end {
+          chunk.samples.truncate((end - chunk.start) as usize * self.channels as usize);
+          wrap = true;
+        }
+      }
+
+      rewound = false;
+
+      let frames = chunk.frames(self.channels);
+
+      if let Err(e) = self.backend.queue(&chunk.samples, self.channels, self.rate) {
+        warn!("cannot queue audio buffer: {:?}", e);
+        self.seek_stream(chunk.start);
+        refused = true;
+        break;
+      }
+
+      self.ring.push(chunk.start, frames);
+
+      if let (true, Some((start, _))) = (wrap, region) {
+        self.seek_stream(start);
+      }
+    }
+
+    if self.ring.is_empty() && self.playing && !refused {
+      // everything was played: the end of the track was reached
+      match self.end_policy {
+        EndPolicy::Stop => {
+          self.playing = false;
+          self.seek_frame(0);
+        },
+        EndPolicy::Hold => {
+          self.playing = false;
+          self.backend.stop();
+        },
+        EndPolicy::Loop => ()
+      }
+    } else if self.playing && !self.ring.is_empty() && !self.backend.is_playing() {
+      // the backend stops by itself if it runs out of buffers
+      self.backend.play();
+    }
+  }
+
+  /// Move the playback to the given frame, dropping everything queued.
+  fn seek_frame(&mut self, frame: u64) {
+    self.backend.stop();
+    self.ring.clear();
+    self.seek_stream(frame);
+    self.update();
+  }
+
+  /// Move the decoding to the given frame.
+  fn seek_stream(&mut self, frame: u64) {
+    if !self.streamer.seek(frame) {
+      warn!("cannot seek the soundtrack to frame {}", frame);
+    }
+  }
+
+  fn frame_to_secs(&self, frame: u64) -> f32 {
+    (frame as f64 * self.speed() as f64 / self.rate as f64) as f32
+  }
+
+  fn secs_to_frame(&self, t: f32) -> u64 {
+    (t as f64 * self.rate as f64 / self.speed() as f64).round() as u64
+  }
+}
+
+impl<'a, 'b> Audio<OpenAlBackend<'a, 'b>> where 'a: 'b {
+  /// Play a soundtrack through OpenAL.
+  pub fn open<P, A, F>(track_path: P, f: F) -> Result<A, AudioError> where P: AsRef<Path>, F: FnOnce(Audio<OpenAlBackend>) -> A {
+    let decoder = open_track(track_path)?;
+
+    deb!("initializing OpenAL");
+
+    let alto = alto::Alto::load_default().map_err(openal_failed)?;
+    let al_device = alto.open(None).map_err(openal_failed)?;
+    let al_ctx = al_device.new_context(None).map_err(openal_failed)?;
+    let backend = OpenAlBackend::new(&al_ctx, RING_LEN).map_err(openal_failed)?;
+
+    Ok(f(Audio::from_decoder(decoder, backend)))
+  }
+}
+
+impl<'a> Audio<Box<Backend + 'a>> {
+  /// Play a soundtrack through OpenAL, falling back to silence.
+  ///
+  /// If OpenAL cannot be initialized, the soundtrack is played with a real time `NullBackend`. If
+  /// the soundtrack cannot be opened, an endless silence is played instead. Either way, the cursor
+  /// moves as if the soundtrack were played.
+  pub fn open_or_silent<P, A, F>(track_path: P, f: F) -> A where P: AsRef<Path>, F: for<'x> FnOnce(Audio<Box<Backend + 'x>>) -> A {
+    let decoder: Box<Decoder> = match open_track(track_path) {
+      Ok(decoder) => decoder,
+      Err(e) => {
+        err!("cannot open soundtrack: {:?}; playing silence", e);
+        Box::new(Silence::new(SILENCE_CHANNELS, SILENCE_RATE))
+      }
+    };
+
+    deb!("initializing OpenAL");
+
+    let alto = alto::Alto::load_default().map_err(openal_failed);
+    let al_device = alto.as_ref().map_err(Clone::clone).and_then(|alto| alto.open(None).map_err(openal_failed));
+    let al_ctx = al_device.as_ref().map_err(Clone::clone).and_then(|device| device.new_context(None).map_err(openal_failed));
+
+    let backend = match al_ctx {
+      Ok(ref ctx) => openal_or_silent(ctx),
+      Err(ref e) => {
+        warn!("{:?}; playing silently", e);
+        silent()
+      }
+    };
+
+    f(Audio::from_decoder(decoder, backend))
+  }
+}
+
+// OpenAL backend on a context, or a silent backend if it cannot be created.
+fn openal_or_silent<'a, 'b>(ctx: &'b alto::Context<'a>) -> Box<Backend + 'b> where 'a: 'b {
+  match OpenAlBackend::new(ctx, RING_LEN) {
+    Ok(backend) => Box::new(backend),
+    Err(e) => {
+      warn!("{:?}; playing silently", openal_failed(e));
+      silent()
+    }
+  }
+}
+
+fn silent<'a>() -> Box<Backend + 'a> {
+  Box::new(NullBackend::realtime())
+}
+
+fn openal_failed(e: alto::AltoError) -> AudioError {
+  AudioError::OpenAlFailed(format!("{:?}", e))
+}
+
+// Open the decoder of a soundtrack file.
+fn open_track<P>(track_path: P) -> Result<Box<Decoder>, AudioError> where P: AsRef<Path> {
+  let path = track_path.as_ref();
+
+  info!("streaming soundtrack {:?}", path);
+
+  let file = File::open(path).map_err(|_| AudioError::FileNotFound(path.to_owned()))?;
+  open_decoder(file)
+}
+
+/// Open a decoder for a stream, picking its format – Ogg Vorbis, WAV or FLAC – out of its content.
+///
+/// Streams too short to hold any header are of an unknown format.
+pub fn open_decoder<R>(mut reader: R) -> Result<Box<Decoder>, AudioError> where R: 'static + Read + Seek {
+  let mut magic = [0; 12];
+
+  if reader.read_exact(&mut magic).is_err() {
+    return Err(AudioError::UnknownFormat);
+  }
+
+  reader.seek(SeekFrom::Start(0)).map_err(|e| AudioError::DecodingFailed(e.to_string()))?;
+
+  if &magic[0..4] == b"OggS" {
+    VorbisDecoder::new(reader).map(|decoder| Box::new(decoder) as Box<Decoder>)
+  } else if &magic[0..4] == b"RIFF" && &magic[8..12] == b"WAVE" {
+    WavDecoder::new(reader).map(|decoder| Box::new(decoder) as Box<Decoder>)
+  } else if &magic[0..4] == b"fLaC" {
+    FlacDecoder::new(reader).map(|decoder| Box::new(decoder) as Box<Decoder>)
+  } else {
+    Err(AudioError::UnknownFormat)
+  }
+}
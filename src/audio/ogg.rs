@@ -0,0 +1,110 @@
+//! Ogg Vorbis decoding.
+
+use std::io::{Read, Seek, SeekFrom};
+use vorbis;
+
+use audio::AudioError;
+use audio::stream::Decoder;
+
+// how far from the end of the stream the last Ogg page is looked for
+const LAST_PAGE_SEARCH_LEN: u64 = 64 * 1024;
+
+/// Streaming Ogg Vorbis decoder.
+pub struct VorbisDecoder<R> where R: Read + Seek {
+  decoder: vorbis::Decoder<R>,
+  rate: u32,
+  channels: u16,
+  len: Option<u64>,
+  // first packet, decoded to read the stream’s properties
+  first: Option<Vec<i16>>
+}
+
+impl<R> VorbisDecoder<R> where R: Read + Seek {
+  /// Start decoding a stream.
+  pub fn new(mut reader: R) -> Result<Self, AudioError> {
+    let len = ogg_len(&mut reader);
+
+    reader.seek(SeekFrom::Start(0)).map_err(|e| AudioError::DecodingFailed(e.to_string()))?;
+
+    let mut decoder = vorbis::Decoder::new(reader).map_err(|e| AudioError::DecodingFailed(format!("{:?}", e)))?;
+
+    let first = match decoder.packets().next() {
+      Some(Ok(packet)) => packet,
+      Some(Err(e)) => return Err(AudioError::DecodingFailed(format!("{:?}", e))),
+      None => return Err(AudioError::DecodingFailed("empty Vorbis stream".to_owned()))
+    };
+
+    Ok(VorbisDecoder {
+      decoder: decoder,
+      rate: first.rate as u32,
+      channels: first.channels,
+      len: len,
+      first: Some(first.data)
+    })
+  }
+}
+
+impl<R> Decoder for VorbisDecoder<R> where R: Read + Seek {
+  fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn frames(&self) -> Option<u64> {
+    self.len
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    if let Some(first) = self.first.take() {
+      return Some(first);
+    }
+
+    match self.decoder.packets().next() {
+      Some(Ok(packet)) => Some(packet.data),
+      Some(Err(e)) => {
+        err!("cannot decode Vorbis packet: {:?}", e);
+        None
+      },
+      None => None
+    }
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    self.first = None;
+    self.decoder.time_seek(frame as f64 / self.rate as f64).is_ok()
+  }
+}
+
+/// Length in frames of an Ogg stream, read from the granule position of its last page.
+///
+/// The reader is left at an unspecified position.
+pub fn ogg_len<R>(reader: &mut R) -> Option<u64> where R: Read + Seek {
+  let size = match reader.seek(SeekFrom::End(0)) {
+    Ok(size) => size,
+    Err(_) => return None
+  };
+
+  let search_len = size.min(LAST_PAGE_SEARCH_LEN);
+  let mut tail = Vec::with_capacity(search_len as usize);
+
+  if reader.seek(SeekFrom::Start(size - search_len)).is_err() || reader.by_ref().take(search_len).read_to_end(&mut tail).is_err() {
+    return None;
+  }
+
+  // the page header is the capture pattern, a version, a type and the 64-bit granule position
+  let page = (0..tail.len().saturating_sub(13)).rev().find(|&i| &tail[i..i + 4] == b"OggS");
+
+  page.and_then(|i| {
+    let granule = tail[i + 6..i + 14].iter().rev().fold(0u64, |g, &byte| (g << 8) | byte as u64);
+
+    // -1 means no packet ends on this page
+    if granule == !0 {
+      None
+    } else {
+      Some(granule)
+    }
+  })
+}
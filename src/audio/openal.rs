@@ -0,0 +1,108 @@
+//! OpenAL backend.
+
+use alto::{self, SourceTrait};
+
+use audio::AudioError;
+use audio::backend::Backend;
+
+/// A backend streaming through an OpenAL streaming source.
+pub struct OpenAlBackend<'a, 'b> where 'a: 'b {
+  source: alto::StreamingSource<'a, 'b>,
+  // buffers ready to be filled and queued
+  free_buffers: Vec<alto::Buffer<'a, 'b>>,
+  capacity: usize
+}
+
+impl<'a, 'b> OpenAlBackend<'a, 'b> where 'a: 'b {
+  /// Create a backend with a given number of buffers.
+  pub fn new(ctx: &'b alto::Context<'a>, buffers: usize) -> alto::AltoResult<Self> {
+    let mut free_buffers = Vec::with_capacity(buffers);
+
+    for _ in 0..buffers {
+      free_buffers.push(ctx.new_buffer()?);
+    }
+
+    Ok(OpenAlBackend {
+      source: ctx.new_streaming_source()?,
+      free_buffers: free_buffers,
+      capacity: buffers
+    })
+  }
+}
+
+impl<'a, 'b> Backend for OpenAlBackend<'a, 'b> where 'a: 'b {
+  fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  fn format(&self, channels: u16, rate: u32) -> (u16, u32) {
+    // OpenAL only plays mono and stereo buffers
+    (channels.min(2), rate)
+  }
+
+  fn queue(&mut self, samples: &[i16], channels: u16, rate: u32) -> Result<(), AudioError> {
+    let mut buffer = match self.free_buffers.pop() {
+      Some(buffer) => buffer,
+      None => return Err(AudioError::QueueFailed("no OpenAL buffer available".to_owned()))
+    };
+
+    let set = match channels {
+      1 => buffer.set_data::<alto::Mono<_>, _>(samples, rate as i32),
+      _ => buffer.set_data::<alto::Stereo<_>, _>(samples, rate as i32)
+    };
+
+    if let Err(e) = set {
+      self.free_buffers.push(buffer);
+      return Err(queue_failed(e));
+    }
+
+    // the buffer is handed back when it cannot be queued
+    match self.source.queue_buffer(buffer) {
+      Ok(()) => Ok(()),
+      Err((e, buffer)) => {
+        self.free_buffers.push(buffer);
+        Err(queue_failed(e))
+      }
+    }
+  }
+
+  fn unqueue_processed(&mut self) -> usize {
+    let processed = self.source.buffers_processed().unwrap_or(0);
+    let mut unqueued = 0;
+
+    for _ in 0..processed {
+      if let Ok(buffer) = self.source.unqueue_buffer() {
+        self.free_buffers.push(buffer);
+        unqueued += 1;
+      }
+    }
+
+    unqueued
+  }
+
+  fn stop(&mut self) {
+    // stopping the source marks all its buffers as processed
+    let _ = self.source.stop();
+    self.unqueue_processed();
+  }
+
+  fn offset(&mut self) -> u64 {
+    self.source.sample_offset().unwrap_or(0).max(0) as u64
+  }
+
+  fn play(&mut self) {
+    let _ = self.source.play();
+  }
+
+  fn pause(&mut self) {
+    let _ = self.source.pause();
+  }
+
+  fn is_playing(&mut self) -> bool {
+    self.source.state().ok() == Some(alto::SourceState::Playing)
+  }
+}
+
+fn queue_failed(e: alto::AltoError) -> AudioError {
+  AudioError::QueueFailed(format!("{:?}", e))
+}
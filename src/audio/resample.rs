@@ -0,0 +1,167 @@
+//! Sample rate and channel layout conversion.
+
+use audio::stream::Decoder;
+
+/// A decoder converting the samples of another decoder to a given rate and number of channels.
+///
+/// Rates are converted with linear interpolation. Output channel `c` is the average of the input
+/// channels `i` so that `i % channels == c`, which downmixes stereo to mono; when there are more
+/// output channels than input ones, input channels are repeated, so that mono plays on both sides.
+///
+/// The stream can also be played faster or slower, which changes its pitch as well, like a tape
+/// would. When the conversion has nothing to do, samples are passed through untouched.
+pub struct Resampler<D> {
+  decoder: D,
+  rate: u32,
+  channels: u16,
+  speed: f64,
+  // input frames, already converted to the output channels, not entirely consumed yet
+  input: Vec<f32>,
+  // position of the next output frame in the input, in input frames
+  pos: f64,
+  // whether the decoder hit the end of the stream
+  eos: bool
+}
+
+impl<D> Resampler<D> where D: Decoder {
+  pub fn new(decoder: D, rate: u32, channels: u16) -> Self {
+    Resampler {
+      decoder: decoder,
+      rate: rate.max(1),
+      channels: channels.max(1),
+      speed: 1.,
+      input: Vec::new(),
+      pos: 0.,
+      eos: false
+    }
+  }
+
+  pub fn decoder(&self) -> &D {
+    &self.decoder
+  }
+
+  pub fn speed(&self) -> f32 {
+    self.speed as f32
+  }
+
+  /// Set the playback speed: at `2`, the stream plays twice as fast and an octave higher.
+  ///
+  /// The new speed applies to the frames decoded from now on; seek to drop the decoded ones.
+  pub fn set_speed(&mut self, speed: f32) {
+    self.speed = speed as f64;
+  }
+
+  // number of input frames per output frame
+  fn step(&self) -> f64 {
+    self.decoder.rate() as f64 * self.speed / self.rate as f64
+  }
+
+  // is the conversion a no-op?
+  fn is_identity(&self) -> bool {
+    self.step() == 1. && self.decoder.channels() == self.channels && self.input.is_empty() && self.pos == 0.
+  }
+
+  fn input_frames(&self) -> usize {
+    self.input.len() / self.channels as usize
+  }
+
+  fn push_packet(&mut self, packet: &[i16]) {
+    let from = self.decoder.channels().max(1) as usize;
+    let to = self.channels as usize;
+
+    for frame in packet.chunks(from).filter(|frame| frame.len() == from) {
+      for c in 0..to {
+        if from > to {
+          let mut sum = 0.;
+          let mut n = 0;
+          let mut i = c;
+
+          while i < from {
+            sum += frame[i] as f32;
+            n += 1;
+            i += to;
+          }
+
+          self.input.push(sum / n as f32);
+        } else {
+          self.input.push(frame[c % from] as f32);
+        }
+      }
+    }
+  }
+}
+
+impl<D> Decoder for Resampler<D> where D: Decoder {
+  fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn frames(&self) -> Option<u64> {
+    let step = self.step();
+    self.decoder.frames().map(|frames| (frames as f64 / step).ceil() as u64)
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    if self.is_identity() {
+      return self.decoder.packet();
+    }
+
+    let channels = self.channels as usize;
+    let step = self.step();
+
+    loop {
+      // the frame following the position is needed to interpolate
+      while !self.eos && self.pos as usize + 1 >= self.input_frames() {
+        match self.decoder.packet() {
+          Some(packet) => self.push_packet(&packet),
+          None => self.eos = true
+        }
+      }
+
+      let frames = self.input_frames();
+      let mut output = Vec::new();
+
+      while (self.pos as usize) < frames && (self.eos || self.pos as usize + 1 < frames) {
+        let i = self.pos as usize;
+        let j = (i + 1).min(frames - 1);
+        let t = (self.pos - i as f64) as f32;
+
+        for c in 0..channels {
+          let a = self.input[i * channels + c];
+          let b = self.input[j * channels + c];
+          output.push((a + (b - a) * t).round().max(-32768.).min(32767.) as i16);
+        }
+
+        self.pos += step;
+      }
+
+      // drop the input frames that won’t be interpolated anymore
+      let consumed = (self.pos as usize).min(frames);
+      self.input.drain(..consumed * channels);
+      self.pos -= consumed as f64;
+
+      if !output.is_empty() {
+        return Some(output);
+      }
+
+      if self.eos {
+        return None;
+      }
+    }
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    let pos = frame as f64 * self.step();
+    let input_frame = pos as u64;
+
+    self.input.clear();
+    self.pos = pos - input_frame as f64;
+    self.eos = false;
+
+    self.decoder.seek(input_frame)
+  }
+}
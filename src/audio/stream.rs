@@ -0,0 +1,241 @@
+//! Streaming decoding.
+//!
+//! A soundtrack is decoded a chunk at a time by a `Streamer`, out of any `Decoder`. Each chunk is
+//! uploaded to an audio buffer and queued; a `BufferRing` remembers the frame at which every queued
+//! buffer starts, so that the playback cursor can be computed out of the offset in the queue.
+//!
+//! Nothing in this module requires an audio device.
+
+use std::collections::VecDeque;
+
+/// A source of interleaved 16-bit PCM samples.
+pub trait Decoder {
+  /// Sample rate, in Hz.
+  fn rate(&self) -> u32;
+
+  /// Number of interleaved channels.
+  fn channels(&self) -> u16;
+
+  /// Length of the stream in frames, if known.
+  fn frames(&self) -> Option<u64>;
+
+  /// Decode the next packet of samples. `None` means the end of the stream.
+  fn packet(&mut self) -> Option<Vec<i16>>;
+
+  /// Seek to the given frame. Return `false` if seeking failed.
+  fn seek(&mut self, frame: u64) -> bool;
+}
+
+impl<D> Decoder for Box<D> where D: ?Sized + Decoder {
+  fn rate(&self) -> u32 {
+    (**self).rate()
+  }
+
+  fn channels(&self) -> u16 {
+    (**self).channels()
+  }
+
+  fn frames(&self) -> Option<u64> {
+    (**self).frames()
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    (**self).packet()
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    (**self).seek(frame)
+  }
+}
+
+/// An endless silence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Silence {
+  channels: u16,
+  rate: u32
+}
+
+impl Silence {
+  pub fn new(channels: u16, rate: u32) -> Self {
+    Silence {
+      channels: channels.max(1),
+      rate: rate.max(1)
+    }
+  }
+}
+
+impl Decoder for Silence {
+  fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn frames(&self) -> Option<u64> {
+    None
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    // a tenth of a second
+    Some(vec![0; (self.rate / 10).max(1) as usize * self.channels as usize])
+  }
+
+  fn seek(&mut self, _: u64) -> bool {
+    true
+  }
+}
+
+/// A chunk of decoded samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+  /// Frame at which the chunk starts.
+  pub start: u64,
+  /// Interleaved samples.
+  pub samples: Vec<i16>
+}
+
+impl Chunk {
+  /// Number of frames in the chunk.
+  pub fn frames(&self, channels: u16) -> u64 {
+    (self.samples.len() / channels.max(1) as usize) as u64
+  }
+}
+
+/// Decode a stream in fixed-size chunks.
+pub struct Streamer<D> {
+  decoder: D,
+  chunk_frames: usize,
+  // samples decoded but not yet handed out
+  pending: Vec<i16>,
+  // frame at which the pending samples start
+  pos: u64
+}
+
+impl<D> Streamer<D> where D: Decoder {
+  /// Stream out of a decoder, handing out chunks of `chunk_frames` frames.
+  pub fn new(decoder: D, chunk_frames: usize) -> Self {
+    Streamer {
+      decoder: decoder,
+      chunk_frames: chunk_frames.max(1),
+      pending: Vec::new(),
+      pos: 0
+    }
+  }
+
+  pub fn decoder(&self) -> &D {
+    &self.decoder
+  }
+
+  pub fn decoder_mut(&mut self) -> &mut D {
+    &mut self.decoder
+  }
+
+  /// Frame the next chunk will start at.
+  pub fn pos(&self) -> u64 {
+    self.pos
+  }
+
+  /// Decode the next chunk. The last chunk of the stream might be shorter; `None` means the end of
+  /// the stream.
+  pub fn next_chunk(&mut self) -> Option<Chunk> {
+    let chunk_len = self.chunk_frames * self.decoder.channels().max(1) as usize;
+
+    while self.pending.len() < chunk_len {
+      match self.decoder.packet() {
+        Some(packet) => self.pending.extend(packet),
+        None => break
+      }
+    }
+
+    if self.pending.is_empty() {
+      return None;
+    }
+
+    let rest = if self.pending.len() > chunk_len { self.pending.split_off(chunk_len) } else { Vec::new() };
+    let samples = ::std::mem::replace(&mut self.pending, rest);
+    let chunk = Chunk {
+      start: self.pos,
+      samples: samples
+    };
+
+    self.pos += chunk.frames(self.decoder.channels());
+
+    Some(chunk)
+  }
+
+  /// Seek to the given frame, dropping everything decoded so far.
+  pub fn seek(&mut self, frame: u64) -> bool {
+    self.pending.clear();
+
+    if self.decoder.seek(frame) {
+      self.pos = frame;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+/// Bookkeeping of the buffers queued on a streaming source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BufferRing {
+  // (start frame, frames) of every queued buffer, oldest first
+  queued: VecDeque<(u64, u64)>
+}
+
+impl BufferRing {
+  pub fn new() -> Self {
+    BufferRing {
+      queued: VecDeque::new()
+    }
+  }
+
+  /// Number of queued buffers.
+  pub fn len(&self) -> usize {
+    self.queued.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.queued.is_empty()
+  }
+
+  /// Record a buffer queued at the back of the queue.
+  pub fn push(&mut self, start: u64, frames: u64) {
+    self.queued.push_back((start, frames));
+  }
+
+  /// Record that the oldest buffer was unqueued.
+  pub fn pop(&mut self) -> Option<(u64, u64)> {
+    self.queued.pop_front()
+  }
+
+  /// Forget all the queued buffers.
+  pub fn clear(&mut self) {
+    self.queued.clear();
+  }
+
+  /// Frame being played, given the offset in frames from the start of the oldest queued buffer.
+  ///
+  /// Buffers don’t have to be contiguous: a buffer might start anywhere in the stream, for instance
+  /// when looping. `None` if nothing is queued.
+  pub fn cursor(&self, mut offset: u64) -> Option<u64> {
+    let last = self.queued.len().saturating_sub(1);
+
+    for (i, &(start, frames)) in self.queued.iter().enumerate() {
+      if offset < frames || i == last {
+        return Some(start + offset);
+      }
+
+      offset -= frames;
+    }
+
+    None
+  }
+
+  /// Frame right after the last queued one.
+  pub fn end(&self) -> Option<u64> {
+    self.queued.back().map(|&(start, frames)| start + frames)
+  }
+}
@@ -0,0 +1,194 @@
+//! WAV decoding.
+//!
+//! Integer PCM samples of 8, 16, 24 and 32 bits and 32-bit float samples are supported.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use audio::AudioError;
+use audio::stream::Decoder;
+
+// number of frames decoded at once
+const PACKET_FRAMES: u64 = 4096;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SampleFormat {
+  Int,
+  Float
+}
+
+/// Streaming WAV decoder.
+pub struct WavDecoder<R> where R: Read + Seek {
+  reader: R,
+  format: SampleFormat,
+  bits: u16,
+  rate: u32,
+  channels: u16,
+  // offset of the first sample in the stream
+  data_start: u64,
+  frames: u64,
+  pos: u64
+}
+
+impl<R> WavDecoder<R> where R: Read + Seek {
+  /// Start decoding a stream.
+  pub fn new(mut reader: R) -> Result<Self, AudioError> {
+    let header = read_bytes(&mut reader, 12).ok_or_else(|| failed("truncated WAV header"))?;
+
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+      return Err(AudioError::UnknownFormat);
+    }
+
+    let mut fmt = None;
+
+    // look for the format chunk, then the data chunk
+    loop {
+      let chunk = read_bytes(&mut reader, 8).ok_or_else(|| failed("no data in WAV stream"))?;
+
+      let len = u32_at(&chunk, 4) as u64;
+      let id = &chunk[0..4];
+
+      if id == b"fmt " {
+        fmt = read_bytes(&mut reader, len as usize);
+
+        // chunks are word-aligned
+        if len % 2 == 1 {
+          reader.seek(SeekFrom::Current(1)).map_err(|e| failed(&e.to_string()))?;
+        }
+      } else if id == b"data" {
+        let fmt = match fmt {
+          Some(ref fmt) if fmt.len() >= 16 => fmt,
+          _ => return Err(failed("no valid format chunk in WAV stream"))
+        };
+
+        let data_start = reader.seek(SeekFrom::Current(0)).map_err(|e| failed(&e.to_string()))?;
+
+        return Self::from_fmt(reader, fmt, data_start, len);
+      } else {
+        reader.seek(SeekFrom::Current((len + len % 2) as i64)).map_err(|e| failed(&e.to_string()))?;
+      }
+    }
+  }
+
+  fn from_fmt(reader: R, fmt: &[u8], data_start: u64, data_len: u64) -> Result<Self, AudioError> {
+    // the extensible format stores the actual format at the start of its subformat GUID
+    let tag = match u16_at(fmt, 0) {
+      0xFFFE if fmt.len() >= 26 => u16_at(fmt, 24),
+      tag => tag
+    };
+
+    let channels = u16_at(fmt, 2);
+    let rate = u32_at(fmt, 4);
+    let bits = u16_at(fmt, 14);
+
+    let format = match (tag, bits) {
+      (1, 8) | (1, 16) | (1, 24) | (1, 32) => SampleFormat::Int,
+      (3, 32) => SampleFormat::Float,
+      _ => return Err(failed(&format!("unsupported WAV format {} with {} bits per sample", tag, bits)))
+    };
+
+    if channels == 0 {
+      return Err(failed("WAV stream without channels"));
+    }
+
+    let frame_len = channels as u64 * bits as u64 / 8;
+
+    Ok(WavDecoder {
+      reader: reader,
+      format: format,
+      bits: bits,
+      rate: rate,
+      channels: channels,
+      data_start: data_start,
+      frames: data_len / frame_len,
+      pos: 0
+    })
+  }
+
+  fn frame_len(&self) -> u64 {
+    self.channels as u64 * self.bits as u64 / 8
+  }
+
+  fn sample(&self, bytes: &[u8]) -> i16 {
+    match (self.format, bytes.len()) {
+      (SampleFormat::Int, 1) => ((bytes[0] as i16) - 128) << 8,
+      (SampleFormat::Int, _) => {
+        // keep the two most significant bytes
+        let n = bytes.len();
+        u16_at(bytes, n - 2) as i16
+      },
+      (SampleFormat::Float, _) => {
+        let x = f32::from_bits(u32_at(bytes, 0));
+        (x * 32767.).round().max(-32768.).min(32767.) as i16
+      }
+    }
+  }
+}
+
+impl<R> Decoder for WavDecoder<R> where R: Read + Seek {
+  fn rate(&self) -> u32 {
+    self.rate
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn frames(&self) -> Option<u64> {
+    Some(self.frames)
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    let frames = (self.frames - self.pos).min(PACKET_FRAMES);
+
+    if frames == 0 {
+      return None;
+    }
+
+    let len = (frames * self.frame_len()) as usize;
+
+    let bytes = match read_bytes(&mut self.reader, len) {
+      Some(bytes) => bytes,
+      None => {
+        err!("cannot read WAV samples");
+        return None;
+      }
+    };
+
+    self.pos += frames;
+
+    let sample_len = self.bits as usize / 8;
+    Some(bytes.chunks(sample_len).map(|sample| self.sample(sample)).collect())
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    if frame > self.frames {
+      return false;
+    }
+
+    let offset = self.data_start + frame * self.frame_len();
+
+    if self.reader.seek(SeekFrom::Start(offset)).is_ok() {
+      self.pos = frame;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+fn failed(reason: &str) -> AudioError {
+  AudioError::DecodingFailed(reason.to_owned())
+}
+
+fn read_bytes<R>(reader: &mut R, len: usize) -> Option<Vec<u8>> where R: Read {
+  let mut bytes = vec![0; len];
+  reader.read_exact(&mut bytes).ok().map(|_| bytes)
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+  bytes[offset] as u16 | (bytes[offset + 1] as u16) << 8
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+  u16_at(bytes, offset) as u32 | (u16_at(bytes, offset + 2) as u32) << 16
+}
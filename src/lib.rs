@@ -17,9 +17,10 @@
 //! 
 //! Up to now, the framework provides you with several modules:
 //!
-//! - **audio**: this module gives you the ability to play a soundtrack (no streaming implemented
-//!   yet though; the whole soundtrack is loaded into memory) and interact with basic yet useful
-//!   information about playback (play, pause, toggle, track length, track cursor, etc.)
+//! - **audio**: this module gives you the ability to stream a soundtrack and interact with basic
+//!   yet useful information about playback (play, pause, toggle, track length, track cursor, etc.);
+//!   Ogg Vorbis, WAV and FLAC soundtracks are supported; playback goes through OpenAL or through
+//!   null and offline backends that need no sound card
 //! - **bootstrapping**: this module abstracts over the underlying technologies and provides several
 //!   simple types that can be used to interact with the demo, such as initialization, default
 //!   event handling, and so on
@@ -52,6 +53,7 @@ extern crate cgmath;
 extern crate chrono;
 #[macro_reexport(crate_authors, crate_name, crate_version)]
 extern crate clap;
+extern crate claxon;
 extern crate image;
 pub extern crate luminance;
 extern crate luminance_glfw;
@@ -44,6 +44,16 @@ pub trait Load: 'static + Sized {
   fn load(key: &Self::Key, cache: &mut Store) -> Result<LoadResult<Self>, LoadError>;
 }
 
+/// Savable object to disk.
+///
+/// This is the dual of `Load`: an object that can be saved is written back to the path its key
+/// points to, so that it can be loaded again later – and hot-reloaded if it’s currently in use in a
+/// `Store`.
+pub trait Save: Load {
+  /// Save a resource.
+  fn save(&self, key: &Self::Key) -> Result<(), SaveError>;
+}
+
 /// Result of a resource loading. This type enables you to register a resource for reloading events
 /// of others (dependencies). If you don’t need to run specific code on a dependency reloading, use
 /// the `.into()` function to lift your return value to `LoadResult<_>`.
@@ -80,6 +90,15 @@ pub enum LoadError {
   ConversionFailed(String)
 }
 
+/// Error that might occur while saving a resource.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SaveError {
+  /// The file couldn’t be created or written to.
+  CannotWrite(PathBuf),
+  /// The resource couldn’t be serialized.
+  SerializationFailed(String)
+}
+
 /// Resources are wrapped in this type.
 pub type Res<T> = Rc<RefCell<T>>;
 
@@ -112,6 +131,8 @@ pub struct Store {
   dependencies: HashMap<PathBuf, PathBuf>,
   // vector of pairs (path, timestamp) giving indication on resources to reload
   dirty: Arc<Mutex<Vec<(PathBuf, Instant)>>>,
+  // number of successful reloads so far
+  generation: u64,
   #[allow(dead_code)]
   watcher_thread: thread::JoinHandle<()>
 }
@@ -150,6 +171,7 @@ impl Store {
       metadata: HashMap::new(),
       dependencies: HashMap::new(),
       dirty: dirty,
+      generation: 0,
       watcher_thread: join_handle
     })
   }
@@ -257,6 +279,14 @@ impl Store {
     }
   }
 
+  /// Number of resources reloaded so far.
+  ///
+  /// It changes whenever `sync` reloads a resource, so that objects built out of resources can
+  /// cheaply tell whether they need to be rebuilt.
+  pub fn generation(&self) -> u64 {
+    self.generation
+  }
+
   /// Synchronize the cache by updating the resources that ought to.
   pub fn sync(&mut self) {
     let dirty = self.dirty.clone();
@@ -267,12 +297,17 @@ impl Store {
       if let Some(mut metadata) = self.metadata.remove(&path) {
         if instant.duration_since(metadata.last_update_instant) >= Duration::from_millis(UPDATE_AWAIT_TIME_MS) {
           if (metadata.on_reload)(self).is_ok() {
+            self.generation += 1;
+
             // if we have successfully reloaded the resource, notify the observers that this
             // dependency has changed
             for dep in self.dependencies.get(path.as_path()).cloned() {
               if let Some(obs_metadata) = self.metadata.remove(dep.as_path()) {
-                if let Err(e) = (obs_metadata.on_reload)(self) {
-                  warn!("cannot reload {:?} {:?}", dep, e);
+                match (obs_metadata.on_reload)(self) {
+                  Ok(_) => self.generation += 1,
+                  Err(e) => {
+                    warn!("cannot reload {:?} {:?}", dep, e);
+                  }
                 }
 
                 self.metadata.insert(dep, obs_metadata);
@@ -0,0 +1,631 @@
+extern crate spectra;
+
+use spectra::audio::{Audio, AudioError, EndPolicy, open_decoder};
+use spectra::audio::analysis::*;
+use spectra::audio::backend::*;
+use spectra::audio::beat::*;
+use spectra::audio::ogg::ogg_len;
+use spectra::audio::resample::Resampler;
+use spectra::audio::stream::*;
+use spectra::anim::spline::SplineKey;
+use spectra::sys::resource::{Save, Store};
+use std::env::temp_dir;
+use std::f32::consts::PI;
+use std::io::Cursor;
+
+// A stereo decoder producing packets of 3 frames; each sample is the index of its frame.
+struct Ramp {
+  frames: u64,
+  pos: u64
+}
+
+impl Decoder for Ramp {
+  fn rate(&self) -> u32 {
+    8
+  }
+
+  fn channels(&self) -> u16 {
+    2
+  }
+
+  fn frames(&self) -> Option<u64> {
+    Some(self.frames)
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    if self.pos >= self.frames {
+      return None;
+    }
+
+    let end = (self.pos + 3).min(self.frames);
+    let packet = (self.pos..end).flat_map(|f| vec![f as i16, f as i16]).collect();
+    self.pos = end;
+
+    Some(packet)
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    if frame <= self.frames {
+      self.pos = frame;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+#[test]
+fn streamer_chunks() {
+  let mut streamer = Streamer::new(Ramp { frames: 10, pos: 0 }, 4);
+
+  let chunk = streamer.next_chunk().unwrap();
+  assert_eq!(chunk.start, 0);
+  assert_eq!(chunk.samples, vec![0, 0, 1, 1, 2, 2, 3, 3]);
+
+  let chunk = streamer.next_chunk().unwrap();
+  assert_eq!(chunk.start, 4);
+  assert_eq!(chunk.frames(2), 4);
+  assert_eq!(chunk.samples[0], 4);
+
+  // the last chunk is shorter
+  let chunk = streamer.next_chunk().unwrap();
+  assert_eq!((chunk.start, chunk.frames(2)), (8, 2));
+  assert_eq!(streamer.next_chunk(), None);
+
+  assert!(streamer.seek(5));
+  let chunk = streamer.next_chunk().unwrap();
+  assert_eq!(chunk.start, 5);
+  assert_eq!(chunk.samples[0], 5);
+
+  assert!(!streamer.seek(42));
+}
+
+#[test]
+fn buffer_ring_cursor() {
+  let mut streamer = Streamer::new(Ramp { frames: 100, pos: 0 }, 10);
+  let mut ring = BufferRing::new();
+
+  for _ in 0..3 {
+    let chunk = streamer.next_chunk().unwrap();
+    ring.push(chunk.start, chunk.frames(2));
+  }
+
+  assert_eq!(ring.cursor(4), Some(4));
+  // past the first buffer but before it’s unqueued
+  assert_eq!(ring.cursor(12), Some(12));
+
+  ring.pop();
+  assert_eq!(ring.cursor(2), Some(12));
+  assert_eq!(ring.end(), Some(30));
+
+  // seeking restarts the ring anywhere
+  ring.clear();
+  assert_eq!(ring.cursor(0), None);
+  streamer.seek(55);
+  let chunk = streamer.next_chunk().unwrap();
+  ring.push(chunk.start, chunk.frames(2));
+  assert_eq!(ring.cursor(3), Some(58));
+}
+
+#[test]
+fn ogg_length() {
+  // two fake Ogg pages; only the granule position of the last one matters
+  let page = |granule: u64| {
+    let mut page = b"OggS\x00\x00".to_vec();
+    page.extend((0..8).map(|i| (granule >> (8 * i)) as u8));
+    page.extend(vec![0; 20]);
+    page
+  };
+
+  let mut data = page(1000);
+  data.extend(page(123456));
+
+  assert_eq!(ogg_len(&mut Cursor::new(data)), Some(123456));
+  assert_eq!(ogg_len(&mut Cursor::new(b"not an ogg stream".to_vec())), None);
+}
+
+// Two seconds of stereo 44.1 kHz data; each sample is the index of its frame, wrapped.
+struct Tone {
+  pos: u64
+}
+
+const TONE_FRAMES: u64 = 88200;
+
+impl Decoder for Tone {
+  fn rate(&self) -> u32 {
+    44100
+  }
+
+  fn channels(&self) -> u16 {
+    2
+  }
+
+  fn frames(&self) -> Option<u64> {
+    Some(TONE_FRAMES)
+  }
+
+  fn packet(&mut self) -> Option<Vec<i16>> {
+    if self.pos >= TONE_FRAMES {
+      return None;
+    }
+
+    let end = (self.pos + 1000).min(TONE_FRAMES);
+    let packet = (self.pos..end).flat_map(|f| vec![(f % 30000) as i16; 2]).collect();
+    self.pos = end;
+
+    Some(packet)
+  }
+
+  fn seek(&mut self, frame: u64) -> bool {
+    self.pos = frame;
+    true
+  }
+}
+
+#[test]
+fn null_backend() {
+  let mut audio = Audio::from_decoder(Box::new(Tone { pos: 0 }), NullBackend::new());
+
+  assert_eq!(audio.len(), 2.);
+
+  audio.play();
+
+  // one second at 60 FPS, crossing several streamed buffers
+  for _ in 0..60 {
+    audio.backend_mut().advance(1. / 60.);
+    audio.cursor();
+  }
+
+  assert!((audio.cursor() - 1.).abs() < 1e-3);
+
+  // time doesn’t pass while paused
+  audio.pause();
+  audio.backend_mut().advance(1.);
+  assert!((audio.cursor() - 1.).abs() < 1e-3);
+
+  audio.set_cursor(0.25);
+  assert_eq!(audio.cursor(), 0.5);
+
+  // the track loops
+  audio.play();
+
+  for _ in 0..120 {
+    audio.backend_mut().advance(1. / 60.);
+    audio.cursor();
+  }
+
+  let t = audio.cursor();
+  assert!(t > 0.4 && t < 0.6);
+}
+
+#[test]
+fn offline_backend() {
+  let mut audio = Audio::from_decoder(Box::new(Tone { pos: 0 }), OfflineBackend::new());
+  audio.play();
+
+  // render half a second, a frame at a time
+  for _ in 0..30 {
+    audio.backend_mut().render(735);
+    audio.update();
+  }
+
+  assert!((audio.cursor() - 0.5).abs() < 1e-3);
+
+  let backend = audio.backend();
+  let output = backend.output();
+
+  assert_eq!((backend.channels(), backend.rate()), (2, 44100));
+  assert_eq!(output.len(), 735 * 30 * 2);
+  assert!(output.chunks(2).enumerate().all(|(i, frame)| frame == [i as i16, i as i16]));
+
+  let wav = backend.to_wav();
+  assert_eq!(&wav[0..4], b"RIFF");
+  assert_eq!(&wav[8..16], b"WAVEfmt ");
+  assert_eq!(wav.len(), 44 + output.len() * 2);
+}
+
+// An offline backend refusing its second buffer.
+struct Refusing {
+  backend: OfflineBackend,
+  queued: usize
+}
+
+impl Backend for Refusing {
+  fn capacity(&self) -> usize {
+    self.backend.capacity()
+  }
+
+  fn queue(&mut self, samples: &[i16], channels: u16, rate: u32) -> Result<(), AudioError> {
+    self.queued += 1;
+
+    if self.queued == 2 {
+      Err(AudioError::QueueFailed("refused".to_owned()))
+    } else {
+      self.backend.queue(samples, channels, rate)
+    }
+  }
+
+  fn unqueue_processed(&mut self) -> usize {
+    self.backend.unqueue_processed()
+  }
+
+  fn stop(&mut self) {
+    self.backend.stop()
+  }
+
+  fn offset(&mut self) -> u64 {
+    self.backend.offset()
+  }
+
+  fn play(&mut self) {
+    self.backend.play()
+  }
+
+  fn pause(&mut self) {
+    self.backend.pause()
+  }
+
+  fn is_playing(&mut self) -> bool {
+    self.backend.is_playing()
+  }
+}
+
+#[test]
+fn refused_buffer() {
+  let backend = Refusing { backend: OfflineBackend::new(), queued: 0 };
+  let mut audio = Audio::from_decoder(Box::new(Tone { pos: 0 }), backend);
+  audio.play();
+
+  for _ in 0..30 {
+    audio.backend_mut().backend.render(735);
+    audio.update();
+  }
+
+  // the refused buffer was streamed again, without any gap nor desynchronizing the cursor
+  assert!(audio.backend().queued > 2);
+  assert!((audio.cursor() - 0.5).abs() < 1e-3);
+
+  let output = audio.backend().backend.output();
+  assert_eq!(output.len(), 735 * 30 * 2);
+  assert!(output.chunks(2).enumerate().all(|(i, frame)| frame == [i as i16, i as i16]));
+}
+
+#[test]
+fn resampler() {
+  // stereo at 8 Hz to mono at 16 Hz
+  let mut resampler = Resampler::new(Ramp { frames: 10, pos: 0 }, 16, 1);
+  assert_eq!(resampler.frames(), Some(20));
+
+  let mut samples = Vec::new();
+  while let Some(packet) = resampler.packet() {
+    samples.extend(packet);
+  }
+
+  // odd frames are halfway between two input frames, rounded up
+  assert_eq!(samples.len(), 20);
+  assert!(samples[..19].iter().enumerate().all(|(i, &x)| x == (i as i16 + 1) / 2));
+  assert_eq!(samples[19], 9);
+
+  assert!(resampler.seek(4));
+  assert_eq!(resampler.packet().unwrap()[0], 2);
+
+  // the source rate is honored
+  let audio = Audio::from_decoder(Box::new(Ramp { frames: 16, pos: 0 }), NullBackend::new());
+  assert_eq!((audio.rate(), audio.channels(), audio.len()), (8, 2, 2.));
+}
+
+#[test]
+fn wav_decoding() {
+  // render the tone as mono at 22050 Hz
+  let mut audio = Audio::from_decoder(Box::new(Tone { pos: 0 }), OfflineBackend::with_format(1, 22050));
+  assert_eq!((audio.rate(), audio.channels(), audio.len()), (22050, 1, 2.));
+
+  audio.play();
+  audio.backend_mut().render(1000);
+
+  let mut decoder = open_decoder(Cursor::new(audio.backend().to_wav())).unwrap();
+  assert_eq!((decoder.rate(), decoder.channels(), decoder.frames()), (22050, 1, Some(1000)));
+
+  let samples = decoder.packet().unwrap();
+  assert_eq!(samples.len(), 1000);
+  assert!(samples.iter().enumerate().all(|(i, &x)| x == 2 * i as i16));
+  assert_eq!(decoder.packet(), None);
+
+  assert!(decoder.seek(500));
+  assert_eq!(decoder.packet().unwrap()[0], 1000);
+
+  assert_eq!(open_decoder(Cursor::new(b"not an audio stream".to_vec())).err(), Some(AudioError::UnknownFormat));
+}
+
+// A 16-bit mono FLAC stream of verbatim frames of 256 samples; each sample is its frame modulo 30000.
+//
+// With a seek table, a seek point is written every `seek_every` frames.
+fn flac_ramp(frames: u64, seek_every: Option<u64>) -> Vec<u8> {
+  const BLOCK: u64 = 256;
+
+  let blocks = (frames + BLOCK - 1) / BLOCK;
+  let mut flac = b"fLaC".to_vec();
+
+  // streaminfo
+  flac.extend_from_slice(&[0, 0, 0, 34]);
+  flac.extend_from_slice(&[(BLOCK >> 8) as u8, BLOCK as u8, (BLOCK >> 8) as u8, BLOCK as u8, 0, 0, 0, 0, 0, 0]);
+  let info = 44100u64 << 44 | 15 << 36 | frames;
+  flac.extend((0..8).rev().map(|i| (info >> (8 * i)) as u8));
+  flac.extend_from_slice(&[0; 16]);
+
+  // some padding to skip, then the seek table, filled once the frames are known
+  flac.extend_from_slice(&[if seek_every.is_some() { 0x01 } else { 0x81 }, 0, 0, 3, 0, 0, 0]);
+  let points = seek_every.map_or(0, |every| (blocks + every - 1) / every);
+  let table_start = flac.len();
+
+  if seek_every.is_some() {
+    flac.extend_from_slice(&[0x83, 0, 0, points as u8 * 18]);
+    flac.extend(::std::iter::repeat(0).take(points as usize * 18));
+  }
+
+  let frames_start = flac.len();
+  let mut point = 0;
+
+  for block in 0..blocks {
+    let start = block * BLOCK;
+    let len = BLOCK.min(frames - start);
+    let frame_start = flac.len();
+
+    if seek_every.map_or(false, |every| block % every == 0) {
+      let at = table_start + 4 + point * 18;
+      let offset = (frame_start - frames_start) as u64;
+
+      for i in 0..8 {
+        flac[at + i] = (start >> (56 - 8 * i)) as u8;
+        flac[at + 8 + i] = (offset >> (56 - 8 * i)) as u8;
+      }
+
+      flac[at + 16] = (len >> 8) as u8;
+      flac[at + 17] = len as u8;
+      point += 1;
+    }
+
+    // block size read at the end of the header, sample rate and sample size from the streaminfo
+    flac.extend_from_slice(&[0xFF, 0xF8, 0x70, 0x08]);
+
+    if block < 0x80 {
+      flac.push(block as u8);
+    } else {
+      flac.push(0xC0 | (block >> 6) as u8);
+      flac.push(0x80 | (block & 0x3F) as u8);
+    }
+
+    flac.push(((len - 1) >> 8) as u8);
+    flac.push((len - 1) as u8);
+    let crc = crc8(&flac[frame_start..]);
+    flac.push(crc);
+
+    // verbatim subframe
+    flac.push(0x02);
+
+    for i in start..start + len {
+      let x = (i % 30000) as i16 as u16;
+      flac.push((x >> 8) as u8);
+      flac.push(x as u8);
+    }
+
+    let crc = crc16(&flac[frame_start..]);
+    flac.push((crc >> 8) as u8);
+    flac.push(crc as u8);
+  }
+
+  flac
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+  bytes.iter().fold(0, |crc, &byte| {
+    (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 != 0 { crc << 1 ^ 0x07 } else { crc << 1 })
+  })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+  bytes.iter().fold(0, |crc, &byte| {
+    (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| if crc & 0x8000 != 0 { crc << 1 ^ 0x8005 } else { crc << 1 })
+  })
+}
+
+#[test]
+fn flac_seeking() {
+  let frames = 100_000;
+
+  for &seek_every in &[None, Some(64)] {
+    let mut decoder = open_decoder(Cursor::new(flac_ramp(frames, seek_every))).unwrap();
+    assert_eq!((decoder.rate(), decoder.channels(), decoder.frames()), (44100, 1, Some(frames)));
+    assert_eq!(decoder.packet().unwrap()[..3], [0, 1, 2]);
+
+    // across the whole stream, back and forth, and twice at the same place, like a loop would
+    for &frame in &[70_000, 255, 256, 99_000, 31_000, 12_345, 12_345, 0, 99_500] {
+      assert!(decoder.seek(frame));
+
+      let packet = decoder.packet().unwrap();
+      assert_eq!(packet[0], (frame % 30000) as i16);
+      assert_eq!(packet.len() as u64, 256 - frame % 256);
+
+      if frame + 256 < frames {
+        let next = decoder.packet().unwrap();
+        assert_eq!(next[0], ((frame / 256 + 1) * 256 % 30000) as i16);
+      }
+    }
+
+    // the last frame is shorter
+    assert!(decoder.seek(frames - 1));
+    assert_eq!(decoder.packet(), Some(vec![((frames - 1) % 30000) as i16]));
+    assert_eq!(decoder.packet(), None);
+
+    assert!(!decoder.seek(frames + 1));
+  }
+}
+
+#[test]
+fn spectrum() {
+  // one second of a 1 kHz sine at 8 kHz, starting half a second in
+  let samples = (0..16000).map(|i| if i < 4000 { 0. } else { 0.5 * (2. * PI * (i % 8) as f32 / 8.).sin() }).collect();
+  let mut analyzer = Analyzer::new(Pcm::new(8000, samples), 256);
+
+  assert_eq!(analyzer.bin_freq(32), 1000.);
+
+  let silence = analyzer.analyze(0.25);
+  assert_eq!((silence.rms, silence.peak), (0., 0.));
+  assert!(silence.magnitudes.iter().all(|&m| m == 0.));
+
+  let spectrum = analyzer.analyze(1.);
+  assert_eq!(spectrum.magnitudes.len(), 128);
+  assert!((spectrum.magnitudes[32] - 0.5).abs() < 1e-2);
+  assert!(spectrum.magnitudes.iter().enumerate().all(|(i, &m)| i == 32 || m < spectrum.magnitudes[32]));
+  assert!((spectrum.rms - 0.5 / 2f32.sqrt()).abs() < 1e-2);
+  assert!((spectrum.peak - 0.5).abs() < 1e-3);
+
+  // deterministic
+  assert_eq!(analyzer.analyze(1.), spectrum);
+
+  analyzer.set_bands(vec![Band::new(0., 500.), Band::new(500., 2000.)]);
+  let spectrum = analyzer.analyze(1.5);
+  assert!(spectrum.bands[1] > 100. * spectrum.bands[0]);
+
+  // past the end of the track
+  assert_eq!(analyzer.analyze(3.).peak, 0.);
+}
+
+#[test]
+fn beat_detection() {
+  // 120 BPM clicks at 8 kHz, the first one at 0.25s
+  let samples = (0..66000).map(|i| {
+    let click = (i + 2000) % 4000;
+
+    if i < 2000 || click >= 160 {
+      0.
+    } else {
+      0.8 * (-(click as f32) / 40.).exp() * (2. * PI * (click % 8) as f32 / 8.).sin()
+    }
+  }).collect();
+  let analyzer = Analyzer::new(Pcm::new(8000, samples), 256);
+  let config = BeatConfig { hop: 80, ..BeatConfig::default() };
+
+  let beats = Beats::detect(&analyzer, &config);
+  let clicks: Vec<_> = (0..16).map(|k| 0.25 + 0.5 * k as f32).collect();
+  let near_clicks = |times: &[f32]| times.iter().zip(&clicks).all(|(&t, &click)| (t - click).abs() < 0.05);
+
+  assert!((beats.bpm - 120.).abs() < 1.);
+  assert_eq!(beats.onsets.len(), 16);
+  assert!(near_clicks(&beats.onsets));
+  assert_eq!(beats.beats.len(), 16);
+  assert!(near_clicks(&beats.beats));
+
+  let track = beats.event_track();
+  assert_eq!(track.events.len(), 32);
+  assert_eq!(track.events.iter().filter(|event| event.name == "beat").count(), 16);
+
+  let envelope = onset_envelope(&analyzer, &config);
+  assert!((&envelope).into_iter().all(|key| key.value >= 0. && key.value <= 1.));
+  assert!((&envelope).into_iter().any(|key| key.value == 1.));
+
+  // save and reload through the store
+  let root = temp_dir();
+  let beats_key = BeatsKey(root.join("spectra_beats.json").to_str().unwrap().to_owned());
+  let envelope_key = SplineKey::<f32>::new(root.join("spectra_onsets.json").to_str().unwrap());
+  beats.save(&beats_key).unwrap();
+  envelope.save(&envelope_key).unwrap();
+
+  let mut store = Store::new(&root).unwrap();
+  assert_eq!(*store.get(&beats_key).unwrap().borrow(), beats);
+
+  let loaded = store.get(&envelope_key).unwrap();
+  assert_eq!((&*loaded.borrow()).into_iter().count(), (&envelope).into_iter().count());
+
+  // silence has no beat
+  let silence = Analyzer::new(Pcm::new(8000, vec![0.; 8000]), 256);
+  assert_eq!(Beats::detect(&silence, &config), Beats { bpm: 0., onsets: Vec::new(), beats: Vec::new() });
+}
+
+#[test]
+fn audio_errors() {
+  let path = temp_dir().join("spectra_no_such_soundtrack.ogg");
+
+  match Audio::new(&path, NullBackend::new()) {
+    Err(AudioError::FileNotFound(p)) => assert_eq!(p, path),
+    _ => panic!("missing soundtrack opened")
+  }
+
+  // a truncated WAV stream
+  let wav = b"RIFF\x00\x00\x00\x00WAVEdata".to_vec();
+  match open_decoder(Cursor::new(wav)) {
+    Err(AudioError::DecodingFailed(_)) => (),
+    _ => panic!("truncated WAV stream decoded")
+  }
+
+  // silence still has a working clock
+  let mut audio = Audio::from_decoder(Box::new(Silence::new(2, 44100)), NullBackend::new());
+  audio.play();
+
+  for _ in 0..90 {
+    audio.backend_mut().advance(1. / 30.);
+    audio.cursor();
+  }
+
+  assert!((audio.cursor() - 3.).abs() < 1e-3);
+}
+
+#[test]
+fn playback_controls() {
+  let mut audio = Audio::from_decoder(Box::new(Tone { pos: 0 }), NullBackend::new());
+
+  // advance the playback by a given number of seconds, at 60 FPS
+  fn run(audio: &mut Audio<NullBackend>, secs: f32) {
+    for _ in 0..(secs * 60.).round() as u32 {
+      audio.backend_mut().advance(1. / 60.);
+      audio.cursor();
+    }
+  }
+
+  audio.seek(1.5);
+  assert_eq!(audio.cursor(), 1.5);
+  audio.seek(-1.);
+  assert_eq!(audio.cursor(), 0.);
+
+  // loop a region
+  audio.set_loop_region(Some((0.5, 1.)));
+  assert_eq!(audio.loop_region(), Some((0.5, 1.)));
+  audio.play();
+  run(&mut audio, 1.25);
+  assert!((audio.cursor() - 0.75).abs() < 1e-2);
+
+  // empty regions are ignored
+  audio.set_loop_region(Some((1., 1.)));
+  assert_eq!(audio.loop_region(), Some((0.5, 1.)));
+
+  // double speed
+  audio.set_loop_region(None);
+  audio.seek(0.);
+  audio.set_speed(2.);
+  assert_eq!(audio.speed(), 2.);
+  run(&mut audio, 0.5);
+  assert!((audio.cursor() - 1.).abs() < 1e-2);
+  audio.set_speed(1.);
+
+  // the track loops by default
+  assert_eq!(audio.end_policy(), EndPolicy::Loop);
+  audio.seek(1.5);
+  run(&mut audio, 1.);
+  assert!((audio.cursor() - 0.5).abs() < 1e-2);
+  assert!(audio.is_playing());
+
+  audio.set_end_policy(EndPolicy::Hold);
+  audio.seek(1.5);
+  run(&mut audio, 1.);
+  assert_eq!(audio.cursor(), 2.);
+  assert!(!audio.is_playing());
+
+  // playing again restarts the track
+  audio.set_end_policy(EndPolicy::Stop);
+  audio.play();
+  assert_eq!(audio.cursor(), 0.);
+  audio.seek(1.5);
+  run(&mut audio, 1.);
+  assert_eq!(audio.cursor(), 0.);
+  assert!(!audio.is_playing());
+}
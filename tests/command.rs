@@ -0,0 +1,141 @@
+extern crate spectra;
+
+mod common;
+
+use common::{cut, manifest, overlap};
+use spectra::anim::command::*;
+use spectra::anim::edit::*;
+use spectra::anim::tempo::*;
+
+#[test]
+fn undo_redo() {
+  let original = manifest();
+  let mut editor = TimelineEditor::new(original.clone());
+
+  assert!(!editor.can_undo());
+
+  editor.execute(Command::MoveCut { track: 0, index: 1, inst_time: ManifestTime::Seconds(12.) }).unwrap();
+  editor.execute(Command::TrimCut { track: 0, index: 0, in_time: ManifestTime::Seconds(1.), out_time: ManifestTime::Seconds(5.) }).unwrap();
+  editor.execute(Command::AddTrack { index: 1, track: TrackManifest::new(vec![cut(0., 1., 3., "flash")]) }).unwrap();
+  editor.execute(Command::EditOverlap {
+    index: 0,
+    overlap: overlap(3., 1., "sum")
+  }).unwrap();
+
+  let edited = editor.manifest().clone();
+  assert_eq!(edited.tracks[0].cuts[1].inst_time, ManifestTime::Seconds(12.));
+  assert_eq!((edited.tracks[0].cuts[0].in_time, edited.tracks[0].cuts[0].out_time), (ManifestTime::Seconds(1.), ManifestTime::Seconds(5.)));
+  assert_eq!(edited.tracks[1].cuts[0].clip, "flash");
+  assert_eq!(edited.overlaps[0].inst_time, ManifestTime::Seconds(3.));
+
+  while editor.undo().unwrap() {}
+  assert_eq!(*editor.manifest(), original);
+  assert!(editor.can_redo());
+
+  while editor.redo().unwrap() {}
+  assert_eq!(*editor.manifest(), edited);
+
+  // a new command discards the redo history
+  editor.undo().unwrap();
+  editor.execute(Command::RemoveTrack { index: 0 }).unwrap();
+  assert!(!editor.can_redo());
+  assert_eq!(editor.manifest().tracks.len(), 2);
+}
+
+#[test]
+fn add_remove_cut() {
+  let mut editor = TimelineEditor::new(manifest());
+
+  editor.execute(Command::AddCut { track: 0, index: 2, cut: cut(0., 2., 14., "outro") }).unwrap();
+  editor.execute(Command::RemoveCut { track: 0, index: 0 }).unwrap();
+
+  let clips: Vec<_> = editor.manifest().tracks[0].cuts.iter().map(|c| c.clip.clone()).collect();
+  assert_eq!(clips, vec!["tunnel", "outro"]);
+
+  editor.undo().unwrap();
+  editor.undo().unwrap();
+  assert_eq!(*editor.manifest(), manifest());
+}
+
+#[test]
+fn split_cut() {
+  let mut editor = TimelineEditor::new(manifest());
+
+  editor.execute(Command::SplitCut { track: 0, index: 0, at: 4. }).unwrap();
+  assert_eq!(editor.manifest().tracks[0].cuts[0], cut(0., 4., 0., "intro"));
+  assert_eq!(editor.manifest().tracks[0].cuts[1], cut(4., 10., 4., "intro"));
+  assert_eq!(editor.manifest().tracks[0].cuts.len(), 3);
+
+  editor.undo().unwrap();
+  assert_eq!(*editor.manifest(), manifest());
+
+  // reversed cuts play the end of their slice first
+  let mut reversed = cut(0., 10., 0., "intro");
+  reversed.time_mapping.reverse = true;
+  editor.execute(Command::SetCut { track: 0, index: 0, cut: reversed }).unwrap();
+  editor.execute(Command::SplitCut { track: 0, index: 0, at: 4. }).unwrap();
+  assert_eq!((editor.manifest().tracks[0].cuts[0].in_time, editor.manifest().tracks[0].cuts[0].out_time), (ManifestTime::Seconds(6.), ManifestTime::Seconds(10.)));
+  assert_eq!((editor.manifest().tracks[0].cuts[1].in_time, editor.manifest().tracks[0].cuts[1].out_time), (ManifestTime::Seconds(0.), ManifestTime::Seconds(6.)));
+
+  assert_eq!(editor.execute(Command::SplitCut { track: 0, index: 0, at: 20. }), Err(CommandError::CannotSplit(20.)));
+
+  // times written in bars and beats stay so
+  let bar = |bar| ManifestTime::Musical(MusicalTime::new(bar, 0.));
+  let mut musical = manifest();
+  musical.tempo = TempoMap::constant(120., 4);
+  musical.tracks[0].cuts[0] = CutManifest {
+    in_time: bar(0),
+    out_time: bar(5),
+    inst_time: bar(0),
+    ..cut(0., 10., 0., "intro")
+  };
+
+  let mut editor = TimelineEditor::new(musical);
+  editor.execute(Command::SplitCut { track: 0, index: 0, at: 4. }).unwrap();
+
+  let cuts = &editor.manifest().tracks[0].cuts;
+  assert_eq!((cuts[0].in_time, cuts[0].out_time, cuts[0].inst_time), (bar(0), bar(2), bar(0)));
+  assert_eq!((cuts[1].in_time, cuts[1].out_time, cuts[1].inst_time), (bar(2), bar(5), bar(2)));
+}
+
+#[test]
+fn failing_commands() {
+  let mut editor = TimelineEditor::new(manifest());
+
+  assert_eq!(editor.execute(Command::RemoveCut { track: 3, index: 0 }), Err(CommandError::NoSuchTrack(3)));
+  assert_eq!(editor.execute(Command::TrimCut { track: 0, index: 0, in_time: ManifestTime::Seconds(2.), out_time: ManifestTime::Seconds(1.) }), Err(CommandError::InvertedCut(2., 1.)));
+
+  // a failing batch is rolled back
+  let batch = Command::Batch(vec![
+    Command::RemoveCut { track: 0, index: 1 },
+    Command::RemoveOverlap { index: 4 }
+  ]);
+
+  assert_eq!(editor.execute(batch), Err(CommandError::NoSuchOverlap(4)));
+  assert_eq!(*editor.manifest(), manifest());
+  assert!(!editor.can_undo());
+}
+
+#[test]
+fn undo_invalid_cut() {
+  // undoing restores the previous state as is, even an inverted cut
+  let mut inverted = manifest();
+  inverted.tracks[0].cuts[0] = cut(3., 2., 0., "intro");
+  let mut editor = TimelineEditor::new(inverted.clone());
+
+  editor.execute(Command::MoveCut { track: 0, index: 1, inst_time: ManifestTime::Seconds(12.) }).unwrap();
+  editor.execute(Command::SetCut { track: 0, index: 0, cut: cut(2., 3., 0., "intro") }).unwrap();
+  let fixed = editor.manifest().clone();
+
+  assert_eq!(editor.undo(), Ok(true));
+  assert_eq!(editor.manifest().tracks[0].cuts[0], cut(3., 2., 0., "intro"));
+  assert_eq!(editor.undo(), Ok(true));
+  assert_eq!(*editor.manifest(), inverted);
+  assert_eq!(editor.undo(), Ok(false));
+
+  while editor.redo().unwrap() {}
+  assert_eq!(*editor.manifest(), fixed);
+
+  // but commands are still checked
+  assert_eq!(editor.execute(Command::SetCut { track: 0, index: 0, cut: cut(3., 2., 0., "intro") }), Err(CommandError::InvertedCut(3., 2.)));
+}
@@ -0,0 +1,66 @@
+//! Fixtures shared by the timeline tests.
+//!
+//! The manifest plays `intro` then `tunnel` on a first track and `tunnel` again on a second one,
+//! with a `sum` overlap where the two tracks meet. `intro` plays `1` and `tunnel` plays `2`.
+
+#![allow(dead_code)]
+
+use spectra::anim::edit::*;
+use spectra::anim::tempo::ManifestTime;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A cut with times in seconds.
+pub fn cut(in_time: Time, out_time: Time, inst_time: Time, clip: &str) -> CutManifest {
+  CutManifest {
+    in_time: ManifestTime::Seconds(in_time),
+    out_time: ManifestTime::Seconds(out_time),
+    inst_time: ManifestTime::Seconds(inst_time),
+    clip: clip.to_owned(),
+    ..CutManifest::default()
+  }
+}
+
+/// An overlap with times in seconds.
+pub fn overlap(inst_time: Time, dur: Time, fold: &str) -> OverlapManifest {
+  OverlapManifest {
+    inst_time: ManifestTime::Seconds(inst_time),
+    dur: ManifestTime::Seconds(dur),
+    fold: fold.to_owned()
+  }
+}
+
+pub fn manifest() -> TimelineManifest {
+  TimelineManifest {
+    tracks: vec![
+      TrackManifest::new(vec![cut(0., 10., 0., "intro"), cut(2., 4., 10., "tunnel")]),
+      TrackManifest::new(vec![cut(0., 3., 8., "tunnel")])
+    ],
+    overlaps: vec![overlap(8., 3., "sum")],
+    ..TimelineManifest::default()
+  }
+}
+
+/// Clips of the manifest, by name.
+pub fn clips() -> HashMap<String, Rc<Clip<i32>>> {
+  let mut clips = HashMap::new();
+  clips.insert("intro".to_owned(), Rc::new(Clip::new("intro", |_| 1)));
+  clips.insert("tunnel".to_owned(), Rc::new(Clip::new("tunnel", |_| 2)));
+  clips
+}
+
+/// Folds of the manifest, by name.
+pub fn folds() -> HashMap<String, Rc<Fold<i32>>> {
+  let mut folds = HashMap::new();
+  folds.insert("sum".to_owned(), Rc::new(Fold::new("sum", |nodes: Vec<i32>| nodes.into_iter().sum())));
+  folds
+}
+
+/// A registry holding the clips and folds of the manifest.
+pub fn registry() -> ClipRegistry<i32> {
+  let mut registry = ClipRegistry::new();
+  registry.add_clip(Clip::new("intro", |_| 1));
+  registry.add_clip(Clip::new("tunnel", |_| 2));
+  registry.add_fold(Fold::new("sum", |nodes: Vec<i32>| nodes.into_iter().sum()));
+  registry
+}
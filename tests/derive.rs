@@ -0,0 +1,37 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate spectra;
+#[macro_use]
+extern crate spectra_derive;
+
+use spectra::anim::spline::*;
+use spectra::linear::V3;
+
+#[derive(Clone, Copy, Debug, Interpolate, PartialEq, SplineDeserializerAdapter)]
+struct FogSettings {
+  density: f32,
+  color: V3<f32>,
+  height: f32
+}
+
+#[test]
+fn derive_interpolate() {
+  let a = FogSettings { density: 0., color: V3::new(0., 0., 1.), height: 10. };
+  let b = FogSettings { density: 1., color: V3::new(1., 0., 0.), height: 20. };
+  let spline = Spline::from_keys(vec![
+    Key::new(0., a, Interpolation::Linear),
+    Key::new(2., b, Interpolation::Linear)
+  ]);
+
+  assert_eq!(spline.sample(1.), Some(FogSettings { density: 0.5, color: V3::new(0.5, 0., 0.5), height: 15. }));
+}
+
+#[test]
+fn derive_spline_deserializer_adapter() {
+  let json = r#"{ "density": 0.25, "color": [1, 0.5, 0], "height": 3 }"#;
+  let de = serde_json::from_str(json).unwrap();
+
+  assert_eq!(FogSettings::from_deserialized(de), FogSettings { density: 0.25, color: V3::new(1., 0.5, 0.), height: 3. });
+}
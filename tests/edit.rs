@@ -0,0 +1,666 @@
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+extern crate spectra;
+
+mod common;
+
+use common::{clips, cut, folds, manifest, overlap, registry};
+use spectra::anim::edit::*;
+use spectra::anim::tempo::*;
+use spectra::sys::resource::{Save, Store};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env::temp_dir;
+use std::rc::Rc;
+
+#[test]
+fn manifest_round_trip() {
+  let (mapping, folds) = (clips(), folds());
+
+  let manifest = manifest();
+  let timeline = Timeline::from_manifest(&manifest, &mapping, &folds).unwrap();
+
+  assert_eq!(timeline.to_manifest(), manifest);
+
+  // save and reload through the store
+  let root = temp_dir();
+  let key = TimelineManifestKey(root.join("spectra_timeline_round_trip.json").to_str().unwrap().to_owned());
+  timeline.to_manifest().save(&key).unwrap();
+
+  let mut store = Store::new(&root).unwrap();
+  let loaded = store.get(&key).unwrap();
+
+  assert_eq!(*loaded.borrow(), manifest);
+}
+
+#[test]
+fn manifest_overlaps() {
+  let (mapping, folds) = (clips(), folds());
+
+  let mut manifest = manifest();
+  let timeline = Timeline::from_manifest(&manifest, &mapping, &folds).unwrap();
+
+  match timeline.play(9.) {
+    Played::Resolved(x) => assert_eq!(x, 3),
+    _ => panic!("overlap not applied")
+  }
+
+  // without the overlap, the two active cuts cannot be resolved
+  manifest.overlaps.clear();
+  let timeline = Timeline::from_manifest(&manifest, &mapping, &folds).unwrap();
+
+  match timeline.play(9.) {
+    Played::NoOverlap => (),
+    _ => panic!("unexpected overlap")
+  }
+}
+
+#[test]
+fn unknown_clip() {
+  let mut mapping = clips();
+  mapping.remove("tunnel");
+
+  match Timeline::from_manifest(&manifest(), &mapping, &HashMap::new()) {
+    Err(e) => assert_eq!(e, TimelineError::UnknownClip("tunnel".to_owned())),
+    Ok(_) => panic!("unknown clip not reported")
+  }
+}
+
+#[test]
+fn live_timeline() {
+  let registry = registry();
+
+  let mut store = Store::new(temp_dir()).unwrap();
+  let manifest = Rc::new(RefCell::new(manifest()));
+  let mut live = LiveTimeline::new(registry, manifest.clone(), &mut store).unwrap();
+
+  match live.play(1., &mut store) {
+    Played::Resolved(x) => assert_eq!(x, 1),
+    _ => panic!("intro should be playing")
+  }
+
+  // edits in place are only picked up once the timeline is invalidated
+  manifest.borrow_mut().tracks[0].cuts[0].clip = "tunnel".to_owned();
+  assert_eq!(live.sync(&mut store), Ok(false));
+  live.invalidate();
+
+  match live.play(1., &mut store) {
+    Played::Resolved(x) => assert_eq!(x, 2),
+    _ => panic!("tunnel should be playing")
+  }
+
+  // an invalid reload is reported and the previous version is kept
+  manifest.borrow_mut().tracks[0].cuts[0].clip = "outro".to_owned();
+  live.invalidate();
+
+  assert_eq!(live.sync(&mut store), Err(TimelineError::UnknownClip("outro".to_owned())));
+
+  match live.play(1., &mut store) {
+    Played::Resolved(x) => assert_eq!(x, 2),
+    _ => panic!("tunnel should still be playing")
+  }
+
+  // the invalid manifest is not resolved again until it changes
+  assert_eq!(live.sync(&mut store), Ok(false));
+
+  manifest.borrow_mut().tracks[0].cuts[0].clip = "ending".to_owned();
+  live.invalidate();
+  assert_eq!(live.sync(&mut store), Err(TimelineError::UnknownClip("ending".to_owned())));
+
+  manifest.borrow_mut().tracks[0].cuts[0].clip = "intro".to_owned();
+  live.invalidate();
+  assert_eq!(live.sync(&mut store), Ok(true));
+}
+
+#[test]
+fn clip_local_time() {
+  let clip = Rc::new(Clip::new("time", |t| t));
+  let cut = |mapping| Cut::new(2., 6., 10., clip.clone()).unwrap().with_time_mapping(mapping);
+  let play = |cut| {
+    let mut track = Track::new();
+    track.add_cut(cut);
+    let mut timeline = Timeline::new();
+    timeline.add_track(track);
+    timeline
+  };
+
+  let timeline = play(cut(TimeMapping::default()));
+  match timeline.play(11.) {
+    Played::Resolved(t) => assert_eq!(t, 3.),
+    _ => panic!("cut not played")
+  }
+
+  let fast = cut(TimeMapping { speed: 2., ..TimeMapping::default() });
+  assert_eq!(fast.dur(), 2.);
+  assert_eq!(fast.local_time(11.), 4.);
+
+  let reversed = cut(TimeMapping { reverse: true, ..TimeMapping::default() });
+  assert_eq!(reversed.local_time(10.), 6.);
+  assert_eq!(reversed.local_time(11.), 5.);
+
+  let looping = cut(TimeMapping { looping: true, dur: Some(10.), ..TimeMapping::default() });
+  assert_eq!(looping.dur(), 10.);
+  assert_eq!(looping.local_time(15.), 3.);
+  // loops start over at the beginning of the slice, but the end of the cut shows the end of it
+  let looping = cut(TimeMapping { looping: true, dur: Some(8.), ..TimeMapping::default() });
+  assert_eq!(looping.local_time(14.), 2.);
+  assert_eq!(looping.local_time(17.5), 5.5);
+  assert_eq!(looping.local_time(18.), 6.);
+
+  match play(looping).play(18.) {
+    Played::Resolved(t) => assert_eq!(t, 6.),
+    _ => panic!("looping cut not played at its end")
+  }
+
+  let reversed_loop = cut(TimeMapping { looping: true, reverse: true, dur: Some(8.), ..TimeMapping::default() });
+  assert_eq!(reversed_loop.local_time(14.), 6.);
+  assert_eq!(reversed_loop.local_time(18.), 2.);
+
+  let frozen = cut(TimeMapping { freeze: Some(4.5), ..TimeMapping::default() });
+  assert_eq!(frozen.local_time(13.), 4.5);
+}
+
+#[test]
+fn validation() {
+  use spectra::anim::validation::TimelineIssue;
+
+  let manifest = TimelineManifest {
+    tracks: vec![
+      TrackManifest::new(vec![cut(0., 4., 1., "a"), cut(0., 2., 4., "b"), cut(3., 2., 10., "c"), cut(0., 2., 7., "d")]),
+      TrackManifest::new(vec![cut(0., 2., 8., "e")])
+    ],
+    overlaps: vec![overlap(8.5, 1., "sum")],
+    ..TimelineManifest::default()
+  };
+
+  assert_eq!(manifest.validate(), vec![
+    TimelineIssue::InvertedCut { track: 0, clip: "c".to_owned(), in_time: 3., out_time: 2. },
+    TimelineIssue::Gap { range: (0., 1.) },
+    TimelineIssue::OverlappingCuts { track: 0, clips: ("a".to_owned(), "b".to_owned()), range: (4., 5.) },
+    TimelineIssue::MissingOverlap { clips: vec!["a".to_owned(), "b".to_owned()], range: (4., 5.) },
+    TimelineIssue::Gap { range: (6., 7.) },
+    TimelineIssue::MissingOverlap { clips: vec!["d".to_owned(), "e".to_owned()], range: (8., 8.5) }
+  ]);
+
+  // cuts meeting at an instant don’t collide, neither on the same track nor across tracks
+  let touching = TimelineManifest {
+    tracks: vec![
+      TrackManifest::new(vec![cut(0., 2., 0., "a"), cut(0., 2., 2., "b")]),
+      TrackManifest::new(vec![cut(0., 2., 4., "c")])
+    ],
+    ..TimelineManifest::default()
+  };
+
+  assert!(touching.validate().is_empty());
+
+  let mut mapping = HashMap::new();
+  mapping.insert("a".to_owned(), Rc::new(Clip::new("a", |_| 'a')));
+  mapping.insert("b".to_owned(), Rc::new(Clip::new("b", |_| 'b')));
+  mapping.insert("c".to_owned(), Rc::new(Clip::new("c", |_| 'c')));
+  let timeline = Timeline::from_manifest(&touching, &mapping, &HashMap::new()).unwrap();
+
+  for &(t, expected) in &[(1., 'a'), (2., 'b'), (4., 'c'), (6., 'c')] {
+    match timeline.play(t) {
+      Played::Resolved(x) => assert_eq!(x, expected),
+      _ => panic!("cuts collided at {}", t)
+    }
+  }
+
+  // time mappings must give cuts a finite duration
+  let mut stopped = cut(0., 2., 0., "f");
+  stopped.time_mapping.speed = 0.;
+  let mut shrunk = cut(0., 2., 2., "g");
+  shrunk.time_mapping.dur = Some(-1.);
+
+  let invalid = TimelineManifest {
+    tracks: vec![TrackManifest::new(vec![stopped, shrunk])],
+    ..manifest.clone()
+  };
+  let issues = invalid.validate();
+
+  assert_eq!(issues[0], TimelineIssue::InvalidTimeMapping { track: 0, clip: "f".to_owned(), reason: "the speed (0) must not be zero".to_owned() });
+  assert_eq!(issues[1], TimelineIssue::InvalidTimeMapping { track: 0, clip: "g".to_owned(), reason: "the duration (-1) must not be negative".to_owned() });
+
+  let mut mapping = HashMap::new();
+  mapping.insert("f".to_owned(), Rc::new(Clip::new("f", |_| ())));
+
+  match Timeline::from_manifest(&invalid, &mapping, &HashMap::new()) {
+    Err(e) => assert_eq!(e, TimelineError::InvalidTimeMapping("f".to_owned(), "the speed (0) must not be zero".to_owned())),
+    Ok(_) => panic!("zero speed not reported")
+  }
+}
+
+#[test]
+fn tempo_map() {
+  use spectra::anim::validation::TimelineIssue;
+
+  let tempo = TempoMap::new(vec![
+    TempoChange::new(4, 60., 3),
+    TempoChange::new(0, 120., 4)
+  ]);
+
+  // 4 bars of 4 beats at 120 BPM, then 3 beats per bar at 60 BPM
+  assert_eq!(tempo.to_seconds(MusicalTime::new(1, 0.)), 2.);
+  assert_eq!(tempo.to_seconds(MusicalTime::new(2, 1.5)), 4.75);
+  assert_eq!(tempo.to_seconds(MusicalTime::new(4, 0.)), 8.);
+  assert_eq!(tempo.to_seconds(MusicalTime::new(5, 2.)), 13.);
+
+  // beats overflowing a bar follow the next tempo change
+  assert_eq!(tempo.to_seconds(MusicalTime::new(3, 6.)), 10.);
+  assert_eq!(tempo.to_musical(4.75), MusicalTime::new(2, 1.5));
+  assert_eq!(tempo.to_musical(13.), MusicalTime::new(5, 2.));
+
+  let info = tempo.beat_info(13.25);
+  assert_eq!((info.bar, info.beat, info.phase, info.bpm), (5, 2, 0.25, 60.));
+
+  // musical durations spanning a tempo change
+  assert_eq!(tempo.duration(7., ManifestTime::Musical(MusicalTime::new(0, 4.))), 3.);
+  assert_eq!(tempo.duration(7., ManifestTime::Musical(MusicalTime::new(1, 0.))), 3.);
+  assert_eq!(tempo.duration(6., ManifestTime::Musical(MusicalTime::new(1, 5.))), 7.);
+
+  // musical times in a manifest
+  let json = r#"{
+    "tracks": [{ "cuts": [{ "in_time": { "bar": 1 }, "out_time": { "bar": 2 }, "inst_time": { "bar": 4, "beat": 1 }, "clip": "a" }] }],
+    "overlaps": [{ "inst_time": { "bar": 4 }, "dur": { "bar": 1, "beat": 2 }, "fold": "first" }],
+    "tempo": [{ "bar": 0, "bpm": 120 }]
+  }"#;
+  let manifest: TimelineManifest = serde_json::from_str(json).unwrap();
+  let clip = Rc::new(Clip::new("a", |t| t));
+  let mut mapping = HashMap::new();
+  mapping.insert("a".to_owned(), clip.clone());
+  let first = Rc::new(Fold::new("first", |nodes: Vec<Time>| nodes[0]));
+  let mut folds = HashMap::new();
+  folds.insert("first".to_owned(), first.clone());
+  let mut timeline = Timeline::from_manifest(&manifest, &mapping, &folds).unwrap();
+
+  // the cut plays [2; 4] of its clip from 8.5 on, and the overlap lasts 3 seconds from 8
+  match timeline.play(9.5) {
+    Played::Resolved(x) => assert_eq!(x, 3.),
+    _ => panic!("musical cut not played")
+  }
+
+  assert_eq!(timeline.to_manifest(), manifest);
+
+  // times that don’t resolve the same anymore are exported in seconds
+  timeline.set_tempo(TempoMap::constant(60., 4));
+  assert_eq!(timeline.to_manifest().tracks[0].cuts[0].inst_time, ManifestTime::Seconds(8.5));
+  assert_eq!(timeline.to_manifest().overlaps[0].dur, ManifestTime::Seconds(3.));
+
+  // tempos must be positive
+  let mut invalid = manifest.clone();
+  invalid.tempo = TempoMap::new(vec![TempoChange::new(0, 120., 4), TempoChange::new(8, 0., 4)]);
+
+  assert_eq!(invalid.validate()[0], TimelineIssue::InvalidTempo { bar: 8, bpm: 0., beats_per_bar: 4 });
+
+  match Timeline::from_manifest(&invalid, &mapping, &folds) {
+    Err(e) => assert_eq!(e, TimelineError::InvalidTempo(8)),
+    Ok(_) => panic!("invalid tempo not reported")
+  }
+
+  // and so must bars
+  invalid.tempo = TempoMap::new(vec![TempoChange::new(0, 120., 4), TempoChange::new(2, 120., 0)]);
+
+  assert_eq!(invalid.validate()[0], TimelineIssue::InvalidTempo { bar: 2, bpm: 120., beats_per_bar: 0 });
+
+  match Timeline::from_manifest(&invalid, &mapping, &folds) {
+    Err(e) => assert_eq!(e, TimelineError::InvalidTempo(2)),
+    Ok(_) => panic!("empty bars not reported")
+  }
+}
+
+#[test]
+fn nested_timeline() {
+  let json = r#"{
+    "tracks": [{ "cuts": [{ "in_time": 1, "out_time": 3, "inst_time": 10, "clip": "sequence" }] }],
+    "timelines": { "sequence": "sequence.json" }
+  }"#;
+  let manifest: TimelineManifest = serde_json::from_str(json).unwrap();
+  let keys = manifest.sub_timeline_keys();
+
+  assert_eq!(keys.len(), 1);
+  assert_eq!(keys[0].0, "sequence");
+  assert_eq!(keys[0].1, TimelineManifestKey("sequence.json".to_owned()));
+
+  // the nested timeline only plays from 0 to 2
+  let flash = Rc::new(Clip::new("flash", |t| t));
+  let mut sub_timeline = Timeline::new();
+  let mut track = Track::new();
+  track.add_cut(Cut::new(0., 2., 0., flash).unwrap());
+  sub_timeline.add_track(track);
+
+  let sequence = Rc::new(Clip::from_timeline("sequence", sub_timeline));
+  let mut mapping = HashMap::new();
+  mapping.insert("sequence".to_owned(), sequence.clone());
+  let timeline = Timeline::from_manifest(&manifest, &mapping, &HashMap::new()).unwrap();
+
+  let played = |t| {
+    match timeline.play(t) {
+      Played::Resolved(x) => Some(x),
+      _ => None
+    }
+  };
+
+  assert_eq!(played(9.), None);
+  assert_eq!(played(10.5), Some(1.5));
+  assert_eq!(played(11.), Some(2.));
+  // the parent cut is active but the nested timeline has nothing to play anymore
+  assert_eq!(played(11.5), None);
+  assert_eq!(timeline.to_manifest().timelines, manifest.timelines);
+}
+
+#[test]
+fn nested_timeline_store() {
+  let root = temp_dir();
+  let key = |name: &str| TimelineManifestKey(root.join(name).to_str().unwrap().to_owned());
+  let parent_key = key("spectra_nested_parent.json");
+  let child_key = key("spectra_nested_child.json");
+
+  let child: TimelineManifest = serde_json::from_str(r#"{
+    "tracks": [{ "cuts": [{ "in_time": 0, "out_time": 2, "inst_time": 0, "clip": "flash" }] }]
+  }"#).unwrap();
+  child.save(&child_key).unwrap();
+
+  let mut parent: TimelineManifest = serde_json::from_str(r#"{
+    "tracks": [{ "cuts": [{ "in_time": 1, "out_time": 3, "inst_time": 10, "clip": "sequence" }] }]
+  }"#).unwrap();
+  parent.timelines.insert("sequence".to_owned(), child_key.0.clone());
+  parent.save(&parent_key).unwrap();
+
+  let mut registry = ClipRegistry::new();
+  registry.add_clip(Clip::new("flash", |t| t));
+  registry.add_clip(Clip::new("spark", |t| -t));
+
+  let mut store = Store::new(&root).unwrap();
+
+  // a nested timeline that cannot be loaded is reported
+  let mut missing = parent.clone();
+  missing.timelines.insert("sequence".to_owned(), key("spectra_nested_missing.json").0);
+
+  match Timeline::from_registry(&missing, &registry, &mut store) {
+    Err(e) => assert_eq!(e, TimelineError::UnknownTimeline(key("spectra_nested_missing.json").0)),
+    Ok(_) => panic!("missing nested timeline not reported")
+  }
+
+  let manifest = store.get(&parent_key).unwrap();
+  let mut live = LiveTimeline::new(registry, manifest, &mut store).unwrap();
+
+  match live.play(10.5, &mut store) {
+    Played::Resolved(x) => assert_eq!(x, 1.5),
+    _ => panic!("nested timeline not played")
+  }
+
+  // a change of the nested manifest is picked up
+  store.get(&child_key).unwrap().borrow_mut().tracks[0].cuts[0].clip = "spark".to_owned();
+  live.invalidate();
+
+  match live.play(10.5, &mut store) {
+    Played::Resolved(x) => assert_eq!(x, -1.5),
+    _ => panic!("nested timeline not reloaded")
+  }
+
+  // a timeline containing itself is rejected
+  store.get(&child_key).unwrap().borrow_mut().timelines.insert("loop".to_owned(), parent_key.0.clone());
+  live.invalidate();
+
+  assert_eq!(live.sync(&mut store), Err(TimelineError::CyclicTimeline(child_key.0.clone())));
+  assert_eq!(live.sync(&mut store), Ok(false));
+}
+
+#[test]
+fn owned_timeline() {
+  struct Demo {
+    timeline: Timeline<i32>
+  }
+
+  // the clips don’t outlive this function but the timeline does
+  fn build() -> Demo {
+    let registry = registry();
+
+    let mut store = Store::new(temp_dir()).unwrap();
+
+    Demo {
+      timeline: Timeline::from_registry(&manifest(), &registry, &mut store).unwrap()
+    }
+  }
+
+  let mut demo = build();
+
+  match demo.timeline.play(9.) {
+    Played::Resolved(x) => assert_eq!(x, 3),
+    _ => panic!("overlap not resolved")
+  }
+
+  // change the timeline while it’s running
+  let mut track = Track::new();
+  let clip = Rc::new(Clip::new("outro", |_| 10));
+  track.add_cut(Cut::new(0., 5., 20., clip).unwrap());
+  demo.timeline.add_track(track);
+
+  match demo.timeline.play(21.) {
+    Played::Resolved(x) => assert_eq!(x, 10),
+    _ => panic!("outro not played")
+  }
+}
+
+#[test]
+fn events() {
+  let json = r#"{
+    "tracks": [],
+    "events": [
+      { "events": [{ "time": 2, "name": "flash", "payload": { "intensity": 0.5 } }, { "time": 1, "name": "burst" }] },
+      { "events": [{ "time": { "bar": 1 }, "name": "scene" }] }
+    ]
+  }"#;
+  let manifest: TimelineManifest = serde_json::from_str(json).unwrap();
+  let timeline: Timeline<()> = Timeline::from_manifest(&manifest, &HashMap::new(), &HashMap::new()).unwrap();
+  let names = |events: Vec<&Event>| events.into_iter().map(|e| e.name.clone()).collect::<Vec<_>>();
+
+  // forwards, the previous time is excluded and the current one included
+  assert_eq!(names(timeline.events_crossed(0., 1.)), vec!["burst"]);
+  assert_eq!(names(timeline.events_crossed(1., 2.)), vec!["flash", "scene"]);
+  assert_eq!(names(timeline.events_crossed(0.5, 0.9)), Vec::<String>::new());
+
+  // backwards, events are crossed in reverse order
+  assert_eq!(names(timeline.events_crossed(3., 0.)), vec!["flash", "scene", "burst"]);
+  assert_eq!(timeline.events_crossed(1., 2.)[0].payload, json!({ "intensity": 0.5 }));
+
+  // seeking doesn’t fire anything
+  let mut cursor = EventCursor::new(0.);
+  assert_eq!(names(cursor.advance(&timeline, 1.5)), vec!["burst"]);
+  cursor.seek(10.);
+  assert_eq!(names(cursor.advance(&timeline, 11.)), Vec::<String>::new());
+  assert_eq!(names(cursor.advance(&timeline, 1.)), vec!["flash", "scene", "burst"]);
+
+  // events fired by a new cursor include the ones at its time
+  let mut start = EventTrack::new();
+  start.add_event(Event::new(0., "start", json!(null)));
+  let mut from_start: Timeline<()> = Timeline::new();
+  from_start.add_event_track(start);
+
+  let mut cursor = EventCursor::new(0.);
+  assert_eq!(names(cursor.advance(&from_start, 0.)), vec!["start"]);
+  assert_eq!(names(cursor.advance(&from_start, 0.5)), Vec::<String>::new());
+  cursor.seek(0.);
+  assert_eq!(names(cursor.advance(&from_start, 0.5)), vec!["start"]);
+
+  // times are exported as written
+  let exported = timeline.to_manifest();
+  assert_eq!(exported.events[0].events[0].name, "burst");
+  assert_eq!(exported.events[0].events[0].time, ManifestTime::Seconds(1.));
+  assert_eq!(exported.events[1].events[0].time, ManifestTime::Musical(MusicalTime::new(1, 0.)));
+
+  let reloaded: TimelineManifest = serde_json::from_str(&serde_json::to_string(&exported).unwrap()).unwrap();
+  let reloaded = Timeline::<()>::from_manifest(&reloaded, &HashMap::new(), &HashMap::new()).unwrap();
+  assert_eq!(reloaded.to_manifest(), exported);
+  assert_eq!(reloaded.event_tracks()[1].events()[0].time, 2.);
+}
+
+#[test]
+fn track_blending() {
+  use spectra::anim::spline::{Interpolate, Interpolation, Key};
+  use spectra::anim::validation::TimelineIssue;
+
+  let json = r#"{
+    "tracks": [
+      { "cuts": [{ "in_time": 0, "out_time": 2, "inst_time": 6, "clip": "boost" }], "priority": 2, "blend": "replace" },
+      { "cuts": [{ "in_time": 0, "out_time": 10, "inst_time": 0, "clip": "base" }] },
+      {
+        "cuts": [{ "in_time": 0, "out_time": 10, "inst_time": 0, "clip": "layer" }],
+        "priority": 1,
+        "blend": { "crossfade": [{ "t": 0, "value": 0 }, { "t": 10, "value": 1 }] }
+      },
+      { "cuts": [{ "in_time": 0, "out_time": 1, "inst_time": 9, "clip": "glow" }], "priority": 3, "blend": { "fold": "sum" } }
+    ],
+    "overlaps": [{ "inst_time": 2, "dur": 1, "fold": "max" }]
+  }"#;
+  let manifest: TimelineManifest = serde_json::from_str(json).unwrap();
+  let mut registry = ClipRegistry::new();
+  registry.add_clip(Clip::new("base", |_| 1.));
+  registry.add_clip(Clip::new("layer", |_| 3.));
+  registry.add_clip(Clip::new("boost", |_| 10.));
+  registry.add_clip(Clip::new("glow", |_| 0.5));
+  registry.add_fold(Fold::new("sum", |nodes: Vec<f32>| nodes.into_iter().sum()));
+  registry.add_fold(Fold::new("max", |nodes: Vec<f32>| nodes.into_iter().fold(0., f32::max)));
+
+  let mut store = Store::new(temp_dir()).unwrap();
+
+  // crossfading requires a mix function
+  match Timeline::from_registry(&manifest, &registry, &mut store) {
+    Err(e) => assert_eq!(e, TimelineError::MissingMix),
+    Ok(_) => panic!("crossfade without a mix function")
+  }
+
+  registry.set_mix(<f32 as Interpolate>::lerp);
+  let timeline = Timeline::from_registry(&manifest, &registry, &mut store).unwrap();
+  let played = |t| {
+    match timeline.play(t) {
+      Played::Resolved(x) => x,
+      _ => panic!("nothing resolved at {}", t)
+    }
+  };
+
+  assert!((played(5.) - 2.).abs() < 1e-5);
+  // the replacing track has the highest priority, even though it comes first
+  assert_eq!(played(7.), 10.);
+  assert!((played(9.5) - 3.4).abs() < 1e-5);
+  // overlaps override blending
+  assert_eq!(played(2.5), 3.);
+
+  assert!(manifest.validate().is_empty());
+  assert_eq!(timeline.to_manifest(), manifest);
+
+  // overlapping cuts of the same track collide instead of being blended
+  let mut colliding = manifest.clone();
+  let glow = CutManifest { inst_time: ManifestTime::Seconds(9.5), ..colliding.tracks[3].cuts[0].clone() };
+  colliding.tracks[3].cuts.push(glow);
+  let timeline = Timeline::from_registry(&colliding, &registry, &mut store).unwrap();
+
+  match timeline.play(9.75) {
+    Played::NoOverlap => (),
+    _ => panic!("colliding cuts blended")
+  }
+
+  assert!(colliding.validate().contains(&TimelineIssue::MissingOverlap {
+    clips: vec!["base".to_owned(), "layer".to_owned(), "glow".to_owned(), "glow".to_owned()],
+    range: (9.5, 10.)
+  }));
+
+  // Catmull-Rom weights cannot be sampled on their first and last segments: the previous key is held
+  let mut smooth = manifest.clone();
+  smooth.tracks[2].blend = Some(BlendManifest::Crossfade(vec![
+    Key::new(0., 0., Interpolation::CatmullRom),
+    Key::new(4., 0.5, Interpolation::CatmullRom),
+    Key::new(6., 0.5, Interpolation::CatmullRom),
+    Key::new(10., 1., Interpolation::CatmullRom)
+  ]));
+  let timeline = Timeline::from_registry(&smooth, &registry, &mut store).unwrap();
+
+  for i in 0..101 {
+    match timeline.play(i as f64 * 0.1) {
+      Played::Resolved(x) => assert!(x.is_finite()),
+      _ => panic!("nothing resolved at {}", i as f64 * 0.1)
+    }
+  }
+
+  match (timeline.play(1.), timeline.play(8.5)) {
+    (Played::Resolved(a), Played::Resolved(b)) => assert_eq!((a, b), (1., 2.)),
+    _ => panic!("crossfade not resolved")
+  }
+}
+
+#[test]
+fn cut_params() {
+  #[derive(Default, Deserialize)]
+  #[serde(default)]
+  struct TunnelParams {
+    color: f32,
+    speed: f32
+  }
+
+  let json = r#"{
+    "tracks": [{
+      "cuts": [
+        { "in_time": 0, "out_time": 1, "inst_time": 0, "clip": "tunnel", "params": { "color": 1 } },
+        { "in_time": 0, "out_time": 1, "inst_time": 2, "clip": "tunnel", "params": { "color": 2, "speed": 10 } },
+        { "in_time": 0, "out_time": 1, "inst_time": 4, "clip": "tunnel" }
+      ]
+    }]
+  }"#;
+  let manifest: TimelineManifest = serde_json::from_str(json).unwrap();
+  let mut registry = ClipRegistry::new();
+  registry.add_clip(Clip::with_params("tunnel", |t, params: &TunnelParams| params.color + params.speed * t as f32));
+
+  let mut store = Store::new(temp_dir()).unwrap();
+  let manifest = Rc::new(RefCell::new(manifest));
+  let mut live = LiveTimeline::new(registry, manifest.clone(), &mut store).unwrap();
+
+  fn played(live: &mut LiveTimeline<f32>, t: Time, store: &mut Store) -> f32 {
+    match live.play(t, store) {
+      Played::Resolved(x) => x,
+      _ => panic!("nothing resolved at {}", t)
+    }
+  }
+
+  assert_eq!(played(&mut live, 0.5, &mut store), 1.);
+  assert_eq!(played(&mut live, 2.5, &mut store), 7.);
+  // no parameters means default ones
+  assert_eq!(played(&mut live, 4.5, &mut store), 0.);
+
+  // parameters are hot-reloaded along with the manifest
+  manifest.borrow_mut().tracks[0].cuts[0].params = json!({ "color": 3, "speed": 2 });
+  live.invalidate();
+  assert_eq!(played(&mut live, 0.5, &mut store), 4.);
+
+  manifest.borrow_mut().tracks[0].cuts[0].params = json!({ "color": "red" });
+  live.invalidate();
+
+  match live.sync(&mut store) {
+    Err(TimelineError::InvalidParams(clip, _)) => assert_eq!(clip, "tunnel"),
+    _ => panic!("invalid parameters not reported")
+  }
+
+  assert_eq!(live.timeline().to_manifest().tracks[0].cuts[0].params, json!({ "color": 3, "speed": 2 }));
+
+  // clips requiring parameters cannot be cut without them
+  #[derive(Deserialize)]
+  struct FlashParams {
+    intensity: f32
+  }
+
+  let flash = Rc::new(Clip::with_params("flash", |_, params: &FlashParams| params.intensity));
+
+  match Cut::new(0., 1., 0., flash.clone()) {
+    Err(TimelineError::InvalidParams(clip, _)) => assert_eq!(clip, "flash"),
+    _ => panic!("missing parameters not reported")
+  }
+
+  let cut = Cut::with_params(0., 1., 0., flash.clone(), json!({ "intensity": 2 })).unwrap();
+  assert_eq!(cut.params(), &json!({ "intensity": 2 }));
+
+  match Cut::with_params(1., 0., 0., flash, json!({ "intensity": 2 })) {
+    Err(e) => assert_eq!(e, TimelineError::InvertedCut("flash".to_owned())),
+    Ok(_) => panic!("inverted cut not reported")
+  }
+}
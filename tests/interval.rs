@@ -0,0 +1,68 @@
+extern crate rand;
+extern crate spectra;
+
+use rand::{Rng, SeedableRng, XorShiftRng};
+use spectra::anim::interval::IntervalIndex;
+
+#[test]
+fn empty_index() {
+  let index = IntervalIndex::default();
+
+  assert!(index.is_empty());
+  assert_eq!(index.query(0.), Vec::<usize>::new());
+
+  let index = IntervalIndex::new(vec![]);
+  assert_eq!(index.query(-1.), Vec::<usize>::new());
+}
+
+#[test]
+fn boundaries() {
+  let index = IntervalIndex::new(vec![(1., 3.), (5., 5.), (4., 2.)]);
+
+  assert_eq!(index.len(), 3);
+
+  // intervals are closed
+  assert_eq!(index.query(1.), vec![0]);
+  assert_eq!(index.query(3.), vec![0]);
+  assert_eq!(index.query(3.0001), Vec::<usize>::new());
+  assert_eq!(index.query(0.9999), Vec::<usize>::new());
+
+  // empty intervals contain their single point, inverted ones nothing
+  assert_eq!(index.query(5.), vec![1]);
+  assert_eq!(index.query(2.5), vec![0]);
+  assert_eq!(index.query(::std::f64::NAN), Vec::<usize>::new());
+}
+
+#[test]
+fn touching_intervals() {
+  let index = IntervalIndex::new(vec![(2., 4.), (0., 2.), (4., 6.), (0., 6.)]);
+
+  // results are sorted by identifier, whatever the order of the intervals
+  assert_eq!(index.query(2.), vec![0, 1, 3]);
+  assert_eq!(index.query(4.), vec![0, 2, 3]);
+  assert_eq!(index.query(3.), vec![0, 3]);
+  assert_eq!(index.query(6.), vec![2, 3]);
+  assert_eq!(index.query(0.), vec![1, 3]);
+}
+
+#[test]
+fn linear_scan_equivalence() {
+  let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+  for &n in &[1, 2, 10, 100, 500] {
+    // coarse bounds, so that many intervals share or touch bounds
+    let intervals: Vec<_> = (0..n).map(|_| {
+      let a = rng.gen_range(0, 100) as f64 * 0.5;
+      let b = rng.gen_range(0, 100) as f64 * 0.5;
+      if rng.gen_range(0, 10) == 0 { (b, a) } else { (a.min(b), a.max(b)) }
+    }).collect();
+    let index = IntervalIndex::new(intervals.clone());
+
+    for i in -2..210 {
+      let t = i as f64 * 0.25;
+      let expected: Vec<_> = (0..n).filter(|&id| intervals[id].0 <= t && t <= intervals[id].1).collect();
+
+      assert_eq!(index.query(t), expected);
+    }
+  }
+}
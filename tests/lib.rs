@@ -57,3 +57,123 @@ fn keys_sorted() {
     t = key.t;
   }
 }
+
+fn assert_close(a: f32, b: f32) {
+  assert!((a - b).abs() < 1e-4, "{} is not close to {}", a, b);
+}
+
+#[test]
+fn easing_reference_curves() {
+  // reference values from the standard (Penner) easing equations
+  assert_close(EasingKind::Quad.ease(EasingDirection::In, 0.5), 0.25);
+  assert_close(EasingKind::Quad.ease(EasingDirection::Out, 0.5), 0.75);
+  assert_close(EasingKind::Quad.ease(EasingDirection::InOut, 0.25), 0.125);
+  assert_close(EasingKind::Cubic.ease(EasingDirection::In, 0.5), 0.125);
+  assert_close(EasingKind::Cubic.ease(EasingDirection::Out, 0.5), 0.875);
+  assert_close(EasingKind::Cubic.ease(EasingDirection::InOut, 0.75), 0.9375);
+  assert_close(EasingKind::Sine.ease(EasingDirection::In, 0.5), 0.29289);
+  assert_close(EasingKind::Expo.ease(EasingDirection::In, 0.5), 0.03125);
+  assert_close(EasingKind::Expo.ease(EasingDirection::Out, 0.5), 0.96875);
+  assert_close(EasingKind::Circ.ease(EasingDirection::In, 0.5), 0.13397);
+  assert_close(EasingKind::Back.ease(EasingDirection::In, 0.5), -0.08770);
+  assert_close(EasingKind::Back.ease(EasingDirection::Out, 0.5), 1.08770);
+  assert_close(EasingKind::Elastic.ease(EasingDirection::Out, 0.5), 1.01563);
+  assert_close(EasingKind::Bounce.ease(EasingDirection::Out, 0.5), 0.765625);
+  assert_close(EasingKind::Bounce.ease(EasingDirection::In, 0.5), 0.234375);
+}
+
+#[test]
+fn easing_endpoints() {
+  let kinds = [
+    EasingKind::Quad, EasingKind::Cubic, EasingKind::Quart, EasingKind::Quint, EasingKind::Sine,
+    EasingKind::Expo, EasingKind::Circ, EasingKind::Back, EasingKind::Elastic, EasingKind::Bounce
+  ];
+  let dirs = [EasingDirection::In, EasingDirection::Out, EasingDirection::InOut];
+
+  for kind in &kinds {
+    for dir in &dirs {
+      assert_close(kind.ease(*dir, 0.), 0.);
+      assert_close(kind.ease(*dir, 1.), 1.);
+    }
+  }
+}
+
+#[test]
+fn ease() {
+  let spline = Spline::from_keys(vec![
+    Key::new(0., 10., Interpolation::Ease(EasingKind::Quad, EasingDirection::In)),
+    Key::new(10., 20., Interpolation::Linear)
+  ]);
+
+  assert_eq!(spline.sample(0.), Some(10.));
+  assert_eq!(spline.sample(5.), Some(12.5));
+  assert_eq!(spline.sample(10.), None);
+}
+
+#[test]
+fn kochanek_bartels_reduces_to_catmull_rom() {
+  let values = [(0., 0.), (1., 3.), (3., -1.), (4., 2.), (7., 5.)];
+  let cr = Spline::from_keys(values.iter().map(|&(t, v)| Key::new(t, v, Interpolation::CatmullRom)).collect());
+  let kb = Spline::from_keys(values.iter().map(|&(t, v)| Key::new(t, v, Interpolation::KochanekBartels)).collect());
+
+  for i in 0..70 {
+    let t = i as f32 * 0.1;
+
+    match (cr.sample(t), kb.sample(t)) {
+      (Some(a), Some(b)) => assert_close(a, b),
+      (None, None) => (),
+      (a, b) => panic!("mismatch at {}: {:?} vs {:?}", t, a, b)
+    }
+  }
+}
+
+#[test]
+fn kochanek_bartels_tension() {
+  let keys = |tension| {
+    Spline::from_keys(vec![
+      Key::new(0., 0., Interpolation::KochanekBartels),
+      Key::new(1., 0., Interpolation::KochanekBartels).with_tcb(Tcb::new(tension, 0., 0.)),
+      Key::new(2., 1., Interpolation::KochanekBartels).with_tcb(Tcb::new(tension, 0., 0.)),
+      Key::new(3., 1., Interpolation::KochanekBartels)
+    ])
+  };
+
+  // a tension of 1 cancels the tangents, hence a pure smoothstep between the two keys
+  assert_close(keys(1.).sample(1.25).unwrap(), 0.15625);
+  assert_close(keys(1.).sample(1.5).unwrap(), 0.5);
+  assert_close(keys(0.).sample(1.5).unwrap(), 0.5);
+}
+
+#[test]
+fn noise_channel() {
+  use spectra::anim::noise::*;
+
+  let params = NoiseParams { seed: 7, frequency: 3., amplitude: 0.5, octaves: 4, ..NoiseParams::default() };
+  let a: NoiseChannel<f32> = NoiseChannel::new(params);
+  let b: NoiseChannel<f32> = NoiseChannel::new(params);
+  let c: NoiseChannel<f32> = NoiseChannel::new(NoiseParams { seed: 8, ..params });
+
+  let mut differs = false;
+  for i in 0..100 {
+    let t = i as f32 * 0.037;
+    let x = a.sample(t);
+
+    // same seed, same noise
+    assert_eq!(x, b.sample(t));
+    // bounded by the sum of the octaves’ amplitudes
+    assert!(x.abs() <= 0.5 + 0.25 + 0.125 + 0.0625);
+
+    differs = differs || x != c.sample(t);
+  }
+
+  assert!(differs);
+
+  // additive over a spline
+  let spline = Spline::from_keys(vec![
+    Key::new(0., 10., Interpolation::Linear),
+    Key::new(10., 20., Interpolation::Linear)
+  ]);
+
+  assert_close(a.sample_over(&spline, 5.).unwrap(), 15. + a.sample(5.));
+  assert_eq!(a.sample_over(&spline, 11.), None);
+}
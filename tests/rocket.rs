@@ -0,0 +1,117 @@
+extern crate spectra;
+
+use spectra::anim::rocket::*;
+use std::env::temp_dir;
+use std::f64;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn set_key(track: u32, row: u32, value: f32, interpolation: u8) -> Vec<u8> {
+  let mut buf = vec![0];
+  buf.extend_from_slice(&be(track));
+  buf.extend_from_slice(&be(row));
+  buf.extend_from_slice(&be(value.to_bits()));
+  buf.push(interpolation);
+  buf
+}
+
+fn be(x: u32) -> [u8; 4] {
+  [(x >> 24) as u8, (x >> 16) as u8, (x >> 8) as u8, x as u8]
+}
+
+#[test]
+fn track_sample() {
+  let mut track = RocketTrack::new("cam:x");
+  track.set_key(RocketKey::new(0, 0., RocketInterpolation::Linear));
+  track.set_key(RocketKey::new(4, 8., RocketInterpolation::Step));
+  track.set_key(RocketKey::new(8, 0., RocketInterpolation::Smooth));
+  track.set_key(RocketKey::new(12, 1., RocketInterpolation::Ramp));
+  track.set_key(RocketKey::new(16, 2., RocketInterpolation::Linear));
+
+  assert_eq!(track.sample(-3.), 0.);
+  assert_eq!(track.sample(2.), 4.);
+  assert_eq!(track.sample(4.), 8.);
+  assert_eq!(track.sample(7.), 8.);
+  assert_eq!(track.sample(10.), 0.5);
+  assert_eq!(track.sample(14.), 1.25);
+  assert_eq!(track.sample(100.), 2.);
+  assert_eq!(track.sample(f64::NAN), 0.);
+
+  track.delete_key(4);
+  assert_eq!(track.sample(4.), 0.);
+}
+
+#[test]
+fn player_mode() {
+  let base = temp_dir().join("spectra_rocket_player");
+  let mut track = RocketTrack::new("fade");
+  track.set_key(RocketKey::new(0, 0., RocketInterpolation::Linear));
+  track.set_key(RocketKey::new(10, 1., RocketInterpolation::Step));
+
+  let mut path = base.clone().into_os_string();
+  path.push("_fade.track");
+  track.write(&mut std::fs::File::create(&path).unwrap()).unwrap();
+
+  let mut device = SyncDevice::player(base, 10.);
+  let id = device.get_track("fade").unwrap();
+
+  assert_eq!(device.track(id), &track);
+  assert_eq!(device.sample(id, 0.5), 0.5);
+  assert!(device.get_track("missing").is_err());
+}
+
+#[test]
+fn client_mode() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let addr = listener.local_addr().unwrap();
+  let (done_tx, done_rx) = channel();
+
+  // stand-in Rocket editor
+  let server = thread::spawn(move || {
+    let (mut stream, _) = listener.accept().unwrap();
+
+    let mut greeting = [0; 19];
+    stream.read_exact(&mut greeting).unwrap();
+    assert_eq!(&greeting[..], b"hello, synctracker!");
+    stream.write_all(b"hello, demo!").unwrap();
+
+    // GET_TRACK
+    let mut cmd = [0; 5];
+    stream.read_exact(&mut cmd).unwrap();
+    assert_eq!(cmd, [2, 0, 0, 0, 5]);
+    let mut name = [0; 5];
+    stream.read_exact(&mut name).unwrap();
+    assert_eq!(&name, b"cam:y");
+
+    stream.write_all(&set_key(0, 0, 1., 1)).unwrap();
+    stream.write_all(&set_key(0, 8, 3., 1)).unwrap();
+    stream.write_all(&set_key(0, 16, 0., 0)).unwrap();
+    stream.write_all(&[1, 0, 0, 0, 0, 0, 0, 0, 16]).unwrap(); // DELETE_KEY
+    stream.write_all(&[3, 0, 0, 0, 4]).unwrap(); // SET_ROW
+    stream.write_all(&[4, 1]).unwrap(); // PAUSE
+
+    let _ = done_rx.recv();
+  });
+
+  let mut device = SyncDevice::connect(addr, temp_dir().join("spectra_rocket_client"), 8.).unwrap();
+  let id = device.get_track("cam:y").unwrap();
+
+  let start = Instant::now();
+  let mut events = Vec::new();
+  while !events.contains(&SyncEvent::Pause(true)) {
+    assert!(start.elapsed() < Duration::from_secs(5), "timeout waiting for the editor");
+    events.extend(device.update(0.).unwrap());
+    thread::sleep(Duration::from_millis(1));
+  }
+
+  assert_eq!(events, vec![SyncEvent::Seek(4), SyncEvent::Pause(true)]);
+  assert!(device.is_paused());
+  assert_eq!(device.track(id).keys().len(), 2);
+  assert_eq!(device.sample(id, 0.5), 2.);
+
+  done_tx.send(()).unwrap();
+  server.join().unwrap();
+}